@@ -144,6 +144,9 @@ function M.get_file_score(index)
     special_filename_bonus = score.special_filename_bonus or 0,
     frecency_boost = score.frecency_boost or 0,
     distance_penalty = score.distance_penalty or 0,
+    similarity_bonus = score.similarity_bonus or 0,
+    branch_change_bonus = score.branch_change_bonus or 0,
+    relation_bonus = score.relation_bonus or 0,
     match_type = score.match_type or 'unknown',
   }
 end
@@ -157,6 +160,16 @@ function M.access_file(file_path)
   if not ok then vim.notify('Failed to record file access: ' .. result, vim.log.levels.WARN) end
 end
 
+--- Remember the file opened from the results of a query
+--- @param query string The search query
+--- @param selected_path string Path of the opened file
+function M.record_search_selection(query, selected_path)
+  if not M.state.initialized then return end
+
+  local ok, result = pcall(fuzzy.record_search_selection, query, selected_path)
+  if not ok then vim.notify('Failed to record search selection: ' .. result, vim.log.levels.WARN) end
+end
+
 --- Get file content for preview
 --- @param file_path string Path to the file
 --- @return string|nil File content or nil if failed
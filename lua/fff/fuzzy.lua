@@ -5,6 +5,9 @@ local ok, rust_module = pcall(require, 'fff.rust')
 if not ok then error('Failed to load fff.rust module: ' .. rust_module) end
 
 -- export all functions from the Rust module
+M.version = rust_module.version
+M.supported_features = rust_module.supported_features
+M.api_version = rust_module.api_version
 M.init_db = rust_module.init_db
 M.destroy_db = rust_module.destroy_db
 M.access = rust_module.access
@@ -15,18 +18,72 @@ M.get_keyword_range = rust_module.get_keyword_range
 M.guess_edit_range = rust_module.guess_edit_range
 M.get_words = rust_module.get_words
 M.init_file_picker = rust_module.init_file_picker
+M.reset_file_picker = rust_module.reset_file_picker
+M.set_base_path = rust_module.set_base_path
 M.scan_files = rust_module.scan_files
 M.get_cached_files = rust_module.get_cached_files
 M.fuzzy_search_files = rust_module.fuzzy_search_files
+M.fuzzy_search_files_timed = rust_module.fuzzy_search_files_timed
+M.search_files = rust_module.search_files
+M.export_search_results = rust_module.export_search_results
+M.navigate_to_previous_snapshot = rust_module.navigate_to_previous_snapshot
+M.navigate_to_current = rust_module.navigate_to_current
+M.anchored_search_files = rust_module.anchored_search_files
 M.access_file = rust_module.access_file
+M.is_db_externally_modified = rust_module.is_db_externally_modified
+M.get_all_tracked_paths = rust_module.get_all_tracked_paths
+M.record_search_selection = rust_module.record_search_selection
+M.get_search_history = rust_module.get_search_history
+M.clear_search_history = rust_module.clear_search_history
+M.prune_stale_frecency = rust_module.prune_stale_frecency
+M.get_file_diff = rust_module.get_file_diff
+M.get_file_picker_base_path = rust_module.get_file_picker_base_path
+M.get_file_picker_git_workdir = rust_module.get_file_picker_git_workdir
+M.get_stash_count = rust_module.get_stash_count
+M.get_stash_list = rust_module.get_stash_list
+M.shorten_path = rust_module.shorten_path
+M.strip_path_prefix = rust_module.strip_path_prefix
+M.get_file_with_preview = rust_module.get_file_with_preview
 M.add_file = rust_module.add_file
 M.remove_file = rust_module.remove_file
 M.cancel_scan = rust_module.cancel_scan
 M.get_scan_progress = rust_module.get_scan_progress
 M.is_scanning = rust_module.is_scanning
+M.get_slow_directories = rust_module.get_slow_directories
+M.get_ignored_file_count = rust_module.get_ignored_file_count
+M.compact_file_cache = rust_module.compact_file_cache
+M.get_memory_usage_bytes = rust_module.get_memory_usage_bytes
+M.get_detected_language = rust_module.get_detected_language
+M.set_preferred_extensions = rust_module.set_preferred_extensions
+M.get_directory_stats = rust_module.get_directory_stats
+M.save_file_cache = rust_module.save_file_cache
+M.load_file_cache = rust_module.load_file_cache
 M.refresh_git_status = rust_module.refresh_git_status
+M.has_git_changes = rust_module.has_git_changes
+M.git_changed_file_count = rust_module.git_changed_file_count
 M.stop_background_monitor = rust_module.stop_background_monitor
 M.init_tracing = rust_module.init_tracing
+M.get_recent_logs = rust_module.get_recent_logs
 M.wait_for_initial_scan = rust_module.wait_for_initial_scan
+M.set_exclude_extensions = rust_module.set_exclude_extensions
+M.set_include_extensions_only = rust_module.set_include_extensions_only
+M.set_low_memory_threshold_mb = rust_module.set_low_memory_threshold_mb
+M.get_memory_pressure = rust_module.get_memory_pressure
+M.get_watcher_status = rust_module.get_watcher_status
+M.get_debug_info = rust_module.get_debug_info
+M.set_filename_similarity_algorithm = rust_module.set_filename_similarity_algorithm
+M.set_filename_similarity_weight = rust_module.set_filename_similarity_weight
+M.set_branch_change_bonus = rust_module.set_branch_change_bonus
+M.set_config_file_penalty = rust_module.set_config_file_penalty
+M.set_hidden_file_penalty = rust_module.set_hidden_file_penalty
+M.set_frecency_weights = rust_module.set_frecency_weights
+M.set_max_penalty_multiplier = rust_module.set_max_penalty_multiplier
+M.set_snapshot_history_enabled = rust_module.set_snapshot_history_enabled
+M.set_include_submodules = rust_module.set_include_submodules
+M.set_exclude_platform_artifacts = rust_module.set_exclude_platform_artifacts
+M.set_scanner_threads = rust_module.set_scanner_threads
+M.set_query_prefix_enabled = rust_module.set_query_prefix_enabled
+M.set_early_exit_threshold = rust_module.set_early_exit_threshold
+M.set_directory_frecency_weight = rust_module.set_directory_frecency_weight
 
 return M
@@ -542,7 +542,11 @@ local function format_file_display(item, max_width)
   local filename = item.name
   local dir_path = item.directory or ''
 
-  if dir_path == '' and item.relative_path then
+  -- results of `@dir:` queries show their directory relative to the filtered one
+  if item.display_path then
+    dir_path = vim.fn.fnamemodify(item.display_path, ':h')
+    if dir_path == '.' then dir_path = '' end
+  elseif dir_path == '' and item.relative_path then
     local parent_dir = vim.fn.fnamemodify(item.relative_path, ':h')
     if parent_dir ~= '.' and parent_dir ~= '' then dir_path = parent_dir end
   end
@@ -944,6 +948,7 @@ function M.select(action)
 
   local relative_path = vim.fn.fnamemodify(item.path, ':.')
   file_picker.access_file(relative_path)
+  if M.state.query ~= '' then file_picker.record_search_selection(M.state.query, relative_path) end
 
   vim.cmd('stopinsert')
   M.close()
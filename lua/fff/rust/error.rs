@@ -1,11 +1,14 @@
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
 pub enum Error {
-    #[error("Failed to acquire lock for frecency")]
-    AcquireFrecencyLock,
+    #[error("Failed to acquire lock for frecency in {0}")]
+    AcquireFrecencyLock(&'static str),
 
-    #[error("Failed to acquire lock for items by provider")]
-    AcquireItemLock,
+    #[error("Failed to acquire lock for file picker in {0}")]
+    AcquireItemLock(&'static str),
+
+    #[error("Lock was poisoned by a panicking thread in {operation}")]
+    PoisonedLock { operation: &'static str },
 
     #[error("Attempted to use frecency before initialization")]
     UseFrecencyBeforeInit,
@@ -38,9 +41,23 @@ pub enum Error {
 
     #[error("Invalid file path: {0}")]
     InvalidPath(String),
+    #[error("Base path {} is a symlink to a path that does not exist", .0.display())]
+    BrokenSymlink(std::path::PathBuf),
+    #[error("Base path {} is not a directory", .0.display())]
+    NotADirectory(std::path::PathBuf),
 
     #[error("Failed to scan directory: {0}")]
     DirectoryScan(String),
+
+    #[error("Failed to read or write file cache: {0}")]
+    FileCacheIo(#[source] std::io::Error),
+    #[error("Failed to serialize or deserialize file cache: {0}")]
+    FileCacheFormat(#[source] serde_json::Error),
+    #[error("File cache was created for a different base path: {0}")]
+    FileCacheBasePathMismatch(String),
+
+    #[error("Failed to write search results: {0}")]
+    ExportIo(#[source] std::io::Error),
 }
 
 impl From<Error> for mlua::Error {
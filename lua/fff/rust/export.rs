@@ -0,0 +1,124 @@
+use crate::error::Error;
+use crate::git::format_git_status;
+use crate::types::FileItem;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// One relative path per line
+    #[default]
+    Paths,
+    /// JSON array of the files with the fields they have in Lua
+    Json,
+    /// NUL separated relative paths for `xargs -0`
+    Nul,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "paths" => Some(Self::Paths),
+            "json" => Some(Self::Json),
+            "nul" => Some(Self::Nul),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `files` to `output_path` in `format`, replacing the file if it exists
+pub fn export_files(
+    files: &[FileItem],
+    output_path: &Path,
+    format: ExportFormat,
+) -> Result<usize, Error> {
+    let file = File::create(output_path).map_err(Error::ExportIo)?;
+    let mut writer = BufWriter::new(file);
+    match format {
+        ExportFormat::Paths => write_separated(&mut writer, files, b'\n'),
+        ExportFormat::Nul => write_separated(&mut writer, files, b'\0'),
+        ExportFormat::Json => {
+            let entries: Vec<serde_json::Value> = files.iter().map(file_to_json).collect();
+            serde_json::to_writer(&mut writer, &entries).map_err(std::io::Error::from)
+        }
+    }
+    .and_then(|()| writer.flush())
+    .map_err(Error::ExportIo)?;
+
+    Ok(files.len())
+}
+
+fn write_separated(
+    writer: &mut impl Write,
+    files: &[FileItem],
+    separator: u8,
+) -> std::io::Result<()> {
+    for file in files {
+        writer.write_all(file.relative_path.as_bytes())?;
+        writer.write_all(&[separator])?;
+    }
+    Ok(())
+}
+
+fn file_to_json(file: &FileItem) -> serde_json::Value {
+    serde_json::json!({
+        "path": file.path.to_string_lossy(),
+        "relative_path": file.relative_path,
+        "name": file.file_name,
+        "extension": file.extension,
+        "directory": file.directory,
+        "size": file.size,
+        "modified": file.modified,
+        "total_frecency_score": file.total_frecency_score,
+        "git_status": format_git_status(file.git_status),
+        "is_test_file": file.is_test_file,
+        "is_config_file": file.is_config_file,
+        "is_hidden": file.is_hidden,
+        "category": file.category,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_files() {
+        let dir = std::env::temp_dir().join(format!("fff_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output_path = dir.join("results");
+        let files: Vec<FileItem> = ["src/main.rs", "README.md"]
+            .iter()
+            .map(|path| FileItem::new(dir.join(path), &dir, None).unwrap())
+            .collect();
+
+        assert_eq!(
+            export_files(&files, &output_path, ExportFormat::Paths).unwrap(),
+            2
+        );
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "src/main.rs\nREADME.md\n"
+        );
+
+        export_files(&files, &output_path, ExportFormat::Nul).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "src/main.rs\0README.md\0"
+        );
+
+        export_files(&files, &output_path, ExportFormat::Json).unwrap();
+        let exported: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap();
+        assert_eq!(exported[0]["relative_path"], "src/main.rs");
+        assert_eq!(exported[1]["name"], "README.md");
+        assert_eq!(exported[1]["git_status"], "clear");
+
+        assert_eq!(ExportFormat::from_name("JSON"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::from_name("csv"), None);
+        assert!(export_files(&files, &dir.join("missing/results"), ExportFormat::Paths).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,4 +1,21 @@
+use crate::path_utils::relative_to;
+use std::path::Path;
+
 #[derive(Debug, Clone)]
 pub struct FileKey {
     pub path: String,
 }
+
+impl FileKey {
+    /// Absolute paths inside `base_path` are stored relative to it so they share the frecency
+    /// record of the indexed file, any other path is kept as is
+    pub fn normalize(path: String, base_path: Option<&Path>) -> Self {
+        let relative_path = base_path
+            .filter(|_| Path::new(&path).is_absolute())
+            .and_then(|base_path| relative_to(Path::new(&path), base_path));
+
+        Self {
+            path: relative_path.unwrap_or(path),
+        }
+    }
+}
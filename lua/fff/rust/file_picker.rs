@@ -1,30 +1,203 @@
 use crate::error::Error;
 use crate::file_key::FileKey;
-use crate::git::{format_git_status, GitStatusCache};
-use crate::score::match_and_score_files;
-use crate::types::{FileItem, Score, ScoringContext, SearchResult};
+use crate::frecency::FrecencyTracker;
+use crate::git::{
+    diff_against_head, format_git_status, list_stashes, list_submodule_paths, read_branch_changes,
+    GitStatusCache, StashEntry,
+};
+use crate::memory::spawn_memory_monitor;
+use crate::path_utils::{
+    categorize, detect_config_file, detect_project_language, estimate_lines_from_size, file_inode,
+    is_hidden_file, is_in_test_directory, is_macos_resource_fork, is_ntfs_ads, is_test_file_name,
+    language_extensions, normalize_separators, relative_to, relative_within, shorten_path,
+    strip_path_prefix,
+};
+use crate::query::directory_filter;
+use crate::score::{
+    default_max_typos, ACCESS_SCORE_WEIGHT, DEFAULT_MATCH_CHUNK_SIZE, DEFAULT_PARALLEL_THRESHOLD,
+    MODIFICATION_SCORE_WEIGHT,
+};
+use crate::search_mode::SearchMode;
+use crate::types::{
+    CurrentFileData, DirStats, FileItem, Score, ScoringContext, SearchHistoryEntry, SearchResult,
+    SlowDirectory,
+};
 use git2::{Repository, Status, StatusOptions};
-use ignore::{WalkBuilder, WalkState};
-use notify::{EventKind, RecursiveMode};
-use notify_debouncer_full::{new_debouncer, DebounceEventResult, DebouncedEvent};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{
+    new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache,
+};
 use rayon::prelude::*;
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, RwLock,
+    atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    Arc, Condvar, LazyLock, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
 };
 use std::thread;
 use std::time::{Duration, SystemTime};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::FRECENCY;
 
+/// Lowercased extensions that are never indexed
+pub static EXCLUDED_EXTENSIONS: LazyLock<RwLock<HashSet<String>>> =
+    LazyLock::new(|| RwLock::new(HashSet::new()));
+/// When non-empty only these lowercased extensions are indexed, takes priority over the exclusions
+pub static INCLUDED_EXTENSIONS: LazyLock<RwLock<HashSet<String>>> =
+    LazyLock::new(|| RwLock::new(HashSet::new()));
+/// Bonus for files changed on the current branch, branch changes are only computed when non-zero
+pub static BRANCH_CHANGE_BONUS: AtomicI32 = AtomicI32::new(0);
+
+/// Fraction of the average directory frecency added to each file, stored as `f64` bits
+pub static DIRECTORY_FRECENCY_WEIGHT: AtomicU64 = AtomicU64::new(0);
+
+/// Multiplier of the filename similarity bonus, stored as `f64` bits. 0 by default which
+/// leaves the bonus out.
+pub static FILENAME_SIMILARITY_WEIGHT: AtomicU64 = AtomicU64::new(0);
+
+/// High confidence match count after which fuzzy matching stops, `usize::MAX` disables it
+pub static EARLY_EXIT_THRESHOLD: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Whether files removed from the index are kept to search the previous file lists again, off
+/// by default since the removed files stay in memory
+pub static SNAPSHOT_HISTORY_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the scan descends into git submodules, takes effect on the next scan
+pub static INCLUDE_SUBMODULES: AtomicBool = AtomicBool::new(false);
+
+/// Whether macOS resource forks and NTFS alternate data streams are left out of the index
+pub static EXCLUDE_PLATFORM_ARTIFACTS: AtomicBool = AtomicBool::new(true);
+
+/// Set once the watcher recovered the file data from a lock poisoned by a panicking writer
+pub static DATA_INTEGRITY_WARNING: AtomicBool = AtomicBool::new(false);
+
+/// Score subtracted from configuration files, 0 disables the penalty
+pub static CONFIG_FILE_PENALTY: AtomicI32 = AtomicI32::new(0);
+
+/// Score subtracted from hidden files, 0 disables the penalty
+pub static HIDDEN_FILE_PENALTY: AtomicI32 = AtomicI32::new(0);
+
+/// Multipliers of the access and modification frecency when ranking files without a query
+pub static FRECENCY_WEIGHT_ACCESS: AtomicI32 = AtomicI32::new(ACCESS_SCORE_WEIGHT);
+pub static FRECENCY_WEIGHT_MODIFICATION: AtomicI32 = AtomicI32::new(MODIFICATION_SCORE_WEIGHT);
+
+/// Threads walking the file system during a scan, 0 lets the `ignore` crate pick one per CPU
+pub static SCANNER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// On-disk representation of the file cache, git status is never persisted since it
+/// goes stale quickly and frecency scores are recomputed on load
+#[derive(Debug, Serialize, Deserialize)]
+struct FileCache {
+    base_path: PathBuf,
+    files: Vec<CachedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    relative_path: String,
+    file_name: String,
+    extension: String,
+    directory: String,
+    size: u64,
+    modified: u64,
+    is_test_file: bool,
+    /// Missing from caches written before inodes were tracked
+    #[serde(default)]
+    inode: u64,
+}
+
+/// Number of files that appeared or disappeared when the file list was replaced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SnapshotDiff {
+    added: usize,
+    removed: usize,
+}
+
+impl SnapshotDiff {
+    /// Merge-walks two lists sorted by relative path, calling `on_removed` for every file of
+    /// `old` that is missing from `new`
+    fn between(old: &[FileItem], new: &[FileItem], mut on_removed: impl FnMut(&FileItem)) -> Self {
+        let mut diff = Self::default();
+        let (mut old_iter, mut new_iter) = (old.iter().peekable(), new.iter().peekable());
+        loop {
+            match (old_iter.peek(), new_iter.peek()) {
+                (Some(old_file), Some(new_file)) => {
+                    match old_file.relative_path.cmp(&new_file.relative_path) {
+                        std::cmp::Ordering::Less => {
+                            on_removed(old_file);
+                            diff.removed += 1;
+                            old_iter.next();
+                        }
+                        std::cmp::Ordering::Greater => {
+                            diff.added += 1;
+                            new_iter.next();
+                        }
+                        std::cmp::Ordering::Equal => {
+                            old_iter.next();
+                            new_iter.next();
+                        }
+                    }
+                }
+                (Some(_), None) => {
+                    for old_file in old_iter.by_ref() {
+                        on_removed(old_file);
+                        diff.removed += 1;
+                    }
+                }
+                (None, Some(_)) => {
+                    diff.added += new_iter.by_ref().count();
+                }
+                (None, None) => return diff,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct FileSync {
     files: Vec<FileItem>,
     last_update: SystemTime,
     git_status_cache: Option<GitStatusCache>,
     scan_generation: u64,
+    /// Unix seconds of the last time access scores were computed for every file
+    last_frecency_update: u64,
+    /// Top-level subdirectories that took the longest to walk during the last full scan
+    slow_directories: Vec<SlowDirectory>,
+    /// Files the last full scan visited but did not index
+    skipped_file_count: usize,
+    detected_language: Option<&'static str>,
+    preferred_extensions: HashSet<String>,
+    /// Set once preferred extensions were configured, detection no longer replaces them
+    preferred_extensions_overridden: bool,
+    branch_changed_files: HashSet<PathBuf>,
+    /// Number of git stashes when the last full scan ran
+    stash_count: usize,
+    /// How long the last completed full scan took
+    last_scan_duration: Option<Duration>,
+    /// Files removed by the latest updates, oldest first, kept to search the file lists from
+    /// before them while `SNAPSHOT_HISTORY_ENABLED` is set
+    snapshot_history: VecDeque<RemovedFiles>,
+    /// `(inode, index in files)` sorted by inode, files with an unknown inode are left out
+    inode_index: Vec<(u64, usize)>,
+}
+
+/// Number of updates whose removed files are kept in `FileSync::snapshot_history`
+const SNAPSHOT_HISTORY_CAPACITY: usize = 3;
+
+/// `FilePicker::viewed_generation` while searches use the live file list
+const LIVE_FILES: u64 = u64::MAX;
+
+/// Files one update removed from the index
+#[derive(Debug, Clone)]
+struct RemovedFiles {
+    /// `scan_generation` of the file list they were removed from
+    generation: u64,
+    files: Vec<FileItem>,
 }
 
 impl FileSync {
@@ -34,16 +207,172 @@ impl FileSync {
             last_update: SystemTime::UNIX_EPOCH,
             git_status_cache: None,
             scan_generation: 0,
+            last_frecency_update: 0,
+            slow_directories: Vec::new(),
+            skipped_file_count: 0,
+            detected_language: None,
+            preferred_extensions: HashSet::new(),
+            preferred_extensions_overridden: false,
+            branch_changed_files: HashSet::new(),
+            stash_count: 0,
+            last_scan_duration: None,
+            snapshot_history: VecDeque::with_capacity(SNAPSHOT_HISTORY_CAPACITY),
+            inode_index: Vec::new(),
+        }
+    }
+
+    /// Keeps the `files` removed from the list of `generation`
+    fn push_snapshot(&mut self, generation: u64, files: Vec<FileItem>) {
+        if files.is_empty() {
+            return;
+        }
+        if self.snapshot_history.len() == SNAPSHOT_HISTORY_CAPACITY {
+            self.snapshot_history.pop_front();
         }
+        self.snapshot_history
+            .push_back(RemovedFiles { generation, files });
     }
 
-    fn update_files(&mut self, mut files: Vec<FileItem>, git_status_cache: Option<GitStatusCache>) {
+    /// The live file list for `None`, otherwise the list as of `generation`: the live files plus
+    /// the kept ones removed since then. Files created since are included as well, they exist
+    /// after all. Going back further than the oldest kept update gives the list before it.
+    fn snapshot(&self, generation: Option<u64>) -> Cow<'_, [FileItem]> {
+        let Some(generation) = generation else {
+            return Cow::Borrowed(&self.files);
+        };
+
+        let mut files = self.files.clone();
+        files.extend(
+            self.snapshot_history
+                .iter()
+                .filter(|removed| removed.generation >= generation)
+                .flat_map(|removed| &removed.files)
+                .filter(|file| !self.contains_path(&file.relative_path))
+                .cloned(),
+        );
+        files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        files.dedup_by(|a, b| a.relative_path == b.relative_path);
+        Cow::Owned(files)
+    }
+
+    fn update_files(
+        &mut self,
+        files: Vec<FileItem>,
+        git_status_cache: Option<GitStatusCache>,
+    ) -> SnapshotDiff {
+        let keep_removed = SNAPSHOT_HISTORY_ENABLED.load(Ordering::Relaxed);
+        self.update_files_with_history(files, git_status_cache, keep_removed)
+    }
+
+    /// `update_files` keeping the removed files in the snapshot history when `keep_removed`
+    fn update_files_with_history(
+        &mut self,
+        mut files: Vec<FileItem>,
+        git_status_cache: Option<GitStatusCache>,
+        keep_removed: bool,
+    ) -> SnapshotDiff {
         files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
 
+        let mut removed_files = Vec::new();
+        let diff = SnapshotDiff::between(&self.files, &files, |file| {
+            if keep_removed {
+                removed_files.push(file.clone());
+            }
+        });
         self.files = files;
+        self.push_snapshot(self.scan_generation, removed_files);
         self.git_status_cache = git_status_cache;
         self.last_update = SystemTime::now();
         self.scan_generation = self.scan_generation.wrapping_add(1);
+        self.rebuild_inode_index();
+        // the scan computes frecency for every file it creates
+        self.last_frecency_update = unix_now();
+        self.update_directory_frecency();
+        self.update_project_language();
+        diff
+    }
+
+    fn update_project_language(&mut self) {
+        self.detected_language =
+            detect_project_language(self.files.iter().map(|file| file.extension.as_str()));
+        debug!("Detected project language: {:?}", self.detected_language);
+
+        if !self.preferred_extensions_overridden {
+            self.preferred_extensions = self
+                .detected_language
+                .map(language_extensions)
+                .unwrap_or_default()
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect();
+        }
+    }
+
+    /// Recomputes frecency scores for all files, only hitting the database for access scores
+    /// of files accessed since the previous batch update. Modification scores are always
+    /// recomputed because they depend on the git status.
+    fn batch_update_frecency_scores(&mut self) {
+        let Ok(frecency) = FRECENCY.read() else {
+            return;
+        };
+        let Some(ref tracker) = *frecency else {
+            return;
+        };
+
+        let now = unix_now();
+        let recently_accessed = match self.last_frecency_update {
+            0 => None,
+            since => tracker.get_accesses_since(since).ok(),
+        };
+
+        for file in &mut self.files {
+            let file_key = FileKey::from(&*file);
+            let needs_access_update = recently_accessed.as_ref().is_none_or(|keys| {
+                keys.contains(&FrecencyTracker::path_to_hash_bytes(&file_key.path))
+            });
+
+            if needs_access_update {
+                file.access_frecency_score = tracker.get_access_score(&file_key);
+            }
+            file.modification_frecency_score =
+                tracker.get_modification_score(file.modified, format_git_status(file.git_status));
+            file.total_frecency_score =
+                file.access_frecency_score + file.modification_frecency_score;
+        }
+
+        self.last_frecency_update = now;
+        self.update_directory_frecency();
+    }
+
+    /// Sets `parent_directory_frecency` of every file to the average access score of its directory
+    fn update_directory_frecency(&mut self) {
+        let mut directories: HashMap<&str, (i64, i64)> = HashMap::new();
+        for file in &self.files {
+            let (sum, count) = directories.entry(file.directory.as_str()).or_default();
+            *sum += file.access_frecency_score;
+            *count += 1;
+        }
+
+        let averages: HashMap<String, i64> = directories
+            .into_iter()
+            .map(|(directory, (sum, count))| (directory.to_string(), sum / count))
+            .collect();
+
+        for file in &mut self.files {
+            file.parent_directory_frecency = averages.get(&file.directory).copied().unwrap_or(0);
+        }
+    }
+
+    /// Replaces the git status of every file and recomputes the frecency scores depending on it
+    fn apply_git_status(&mut self, git_status_cache: Option<GitStatusCache>) {
+        for file in &mut self.files {
+            file.git_status = git_status_cache
+                .as_ref()
+                .and_then(|git| git.lookup_status(&file.path));
+        }
+
+        self.git_status_cache = git_status_cache;
+        self.batch_update_frecency_scores();
     }
 
     fn contains_path(&self, path: &str) -> bool {
@@ -69,31 +398,325 @@ impl FileSync {
                 );
             }
             Err(pos) => {
+                let inode = file.inode;
                 self.files.insert(pos, file);
                 self.scan_generation = self.scan_generation.wrapping_add(1);
+
+                for (_, index) in &mut self.inode_index {
+                    if *index >= pos {
+                        *index += 1;
+                    }
+                }
+                if inode != 0 {
+                    let index_pos = self.inode_index.partition_point(|(i, _)| *i < inode);
+                    self.inode_index.insert(index_pos, (inode, pos));
+                }
             }
         }
     }
 
-    /// Remove file by path using binary search
-    fn remove_file_by_path(&mut self, path: &str) -> bool {
-        match self.find_file_index(path) {
-            Ok(index) => {
-                self.files.remove(index);
-                self.scan_generation = self.scan_generation.wrapping_add(1);
-                true
+    /// Indexes the files that are not indexed yet in one batch instead of one by one, returning
+    /// how many were added
+    fn insert_files(&mut self, files: Vec<FileItem>) -> usize {
+        let new_files: Vec<FileItem> = files
+            .into_iter()
+            .filter(|file| !self.contains_path(&file.relative_path))
+            .collect();
+
+        let inserted = new_files.len();
+        if inserted == 0 {
+            return 0;
+        }
+
+        self.files.extend(new_files);
+        self.files
+            .sort_unstable_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        self.scan_generation = self.scan_generation.wrapping_add(1);
+        self.rebuild_inode_index();
+        inserted
+    }
+
+    /// Rebuilds `inode_index` after files were removed or replaced
+    fn rebuild_inode_index(&mut self) {
+        self.inode_index.clear();
+        self.inode_index.extend(
+            self.files
+                .iter()
+                .enumerate()
+                .filter(|(_, file)| file.inode != 0)
+                .map(|(index, file)| (file.inode, index)),
+        );
+        self.inode_index.sort_unstable();
+    }
+
+    /// Binary searches the inode index, files loaded from caches without inodes are only found by
+    /// a linear scan. Always `None` for inode 0 which means unknown.
+    fn find_file_by_inode(&self, inode: u64) -> Option<&FileItem> {
+        if inode == 0 {
+            return None;
+        }
+        if self.inode_index.is_empty() {
+            return self.files.iter().find(|file| file.inode == inode);
+        }
+
+        let pos = self.inode_index.partition_point(|(i, _)| *i < inode);
+        self.inode_index
+            .get(pos)
+            .filter(|(i, _)| *i == inode)
+            .map(|&(_, index)| &self.files[index])
+    }
+
+    fn save_to_disk(&self, path: &Path, base_path: &Path) -> Result<(), Error> {
+        let cache = FileCache {
+            base_path: base_path.to_path_buf(),
+            files: self
+                .files
+                .iter()
+                .map(|file| CachedFile {
+                    relative_path: file.relative_path.clone(),
+                    file_name: file.file_name.clone(),
+                    extension: file.extension.clone(),
+                    directory: file.directory.clone(),
+                    size: file.size,
+                    modified: file.modified,
+                    is_test_file: file.is_test_file,
+                    inode: file.inode,
+                })
+                .collect(),
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(Error::FileCacheIo)?;
+        }
+        let file = std::fs::File::create(path).map_err(Error::FileCacheIo)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), &cache)
+            .map_err(Error::FileCacheFormat)?;
+
+        info!(
+            "Saved file cache with {} files to {}",
+            self.files.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    fn load_from_disk(path: &Path, base_path: &Path) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(Error::FileCacheIo)?;
+        let cache: FileCache = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(Error::FileCacheFormat)?;
+
+        if cache.base_path != base_path {
+            return Err(Error::FileCacheBasePathMismatch(
+                cache.base_path.to_string_lossy().into_owned(),
+            ));
+        }
+
+        let files = cache
+            .files
+            .into_par_iter()
+            .map(|cached| {
+                let is_config_file = detect_config_file(&cached.file_name, &cached.extension);
+                // the hidden attribute is picked up by the next scan
+                let is_hidden = is_hidden_file(&cached.file_name, None);
+                let category =
+                    categorize(&cached.file_name, &cached.extension, &cached.directory).name();
+                let estimated_lines = estimate_lines_from_size(cached.size, &cached.extension);
+                let mut file = FileItem {
+                    path: base_path.join(&cached.relative_path),
+                    relative_path: cached.relative_path,
+                    file_name: cached.file_name,
+                    extension: cached.extension,
+                    directory: cached.directory,
+                    size: cached.size,
+                    modified: cached.modified,
+                    access_frecency_score: 0,
+                    modification_frecency_score: 0,
+                    total_frecency_score: 0,
+                    parent_directory_frecency: 0,
+                    git_status: None,
+                    is_current_file: false,
+                    is_test_file: cached.is_test_file,
+                    is_config_file,
+                    is_hidden,
+                    inode: cached.inode,
+                    category,
+                    estimated_lines,
+                };
+                file.update_frecency_scores();
+                file
+            })
+            .collect();
+
+        let mut sync = Self::new();
+        sync.update_files(files, None);
+        info!(
+            "Loaded file cache with {} files from {}",
+            sync.files.len(),
+            path.display()
+        );
+        Ok(sync)
+    }
+
+    /// Aggregated stats per top-level directory, files in the root are grouped under `.`
+    fn stats_by_directory(&self) -> HashMap<String, DirStats> {
+        let mut stats: HashMap<String, DirStats> = HashMap::new();
+
+        for file in &self.files {
+            let top_level = file
+                .directory
+                .split(['/', '\\'])
+                .next()
+                .filter(|component| !component.is_empty())
+                .unwrap_or(".");
+
+            let entry = stats.entry(top_level.to_string()).or_default();
+            entry.file_count += 1;
+            entry.total_size += file.size;
+
+            match file.git_status {
+                Some(status) if status.contains(Status::WT_NEW) => entry.untracked_count += 1,
+                Some(status) if status.intersects(Status::WT_MODIFIED | Status::INDEX_MODIFIED) => {
+                    entry.modified_count += 1
+                }
+                _ => {}
             }
-            Err(_) => false,
         }
+
+        stats
+    }
+
+    /// Removes every file whose relative path is in `paths` with a single merge-walk over the
+    /// sorted files instead of a `Vec::remove` per path, returns the number of removed files
+    fn batch_remove_paths(&mut self, paths: &[&str]) -> usize {
+        let mut paths = paths.to_vec();
+        paths.sort_unstable();
+        paths.dedup();
+
+        let original_len = self.files.len();
+        let mut remaining = paths.iter().peekable();
+        self.files.retain(|file| {
+            while remaining
+                .next_if(|path| **path < file.relative_path.as_str())
+                .is_some()
+            {}
+            remaining
+                .next_if(|path| **path == file.relative_path.as_str())
+                .is_none()
+        });
+
+        let removed = original_len - self.files.len();
+        if removed > 0 {
+            self.scan_generation = self.scan_generation.wrapping_add(1);
+            self.rebuild_inode_index();
+        }
+        removed
+    }
+
+    /// Range of files located under `dir_prefix`, relies on `files` being sorted by relative path
+    fn prefix_range(&self, dir_prefix: &str) -> std::ops::Range<usize> {
+        let prefix = format!(
+            "{}{}",
+            dir_prefix.trim_end_matches(['/', '\\']),
+            std::path::MAIN_SEPARATOR
+        );
+        let start = self
+            .files
+            .partition_point(|file| file.relative_path.as_str() < prefix.as_str());
+        let len =
+            self.files[start..].partition_point(|file| file.relative_path.starts_with(&prefix));
+        start..start + len
+    }
+
+    /// Rough number of bytes held by the file index and the kept removed files: the item slots
+    /// plus the heap allocations of their strings and paths. Only `FileSync` owns the index,
+    /// copies handed out by `get_cached_files` are not included.
+    fn approximate_memory_usage(&self) -> usize {
+        std::iter::once(&self.files)
+            .chain(self.snapshot_history.iter().map(|removed| &removed.files))
+            .map(files_memory_usage)
+            .sum()
+    }
+
+    /// Whether any indexed file is located under `dir_prefix`
+    fn contains_prefix(&self, dir_prefix: &str) -> bool {
+        !self.prefix_range(dir_prefix).is_empty()
+    }
+
+    /// Removes every file under `dir_prefix` at once, returns the number of removed files
+    fn remove_files_with_prefix(&mut self, dir_prefix: &str) -> usize {
+        let range = self.prefix_range(dir_prefix);
+        let removed = range.len();
+        if removed > 0 {
+            self.files.drain(range);
+            self.scan_generation = self.scan_generation.wrapping_add(1);
+            self.rebuild_inode_index();
+        }
+        removed
+    }
+
+    /// Drops files scoring below `min_total_frecency_score`, then keeps only the
+    /// `max_file_count` highest scoring ones when given. Returns the number of removed files,
+    /// they come back with the next full scan.
+    fn compact_snapshot(
+        &mut self,
+        min_total_frecency_score: i64,
+        max_file_count: Option<usize>,
+    ) -> usize {
+        let original_len = self.files.len();
+        self.files
+            .retain(|file| file.total_frecency_score >= min_total_frecency_score);
+
+        if let Some(max_file_count) = max_file_count.filter(|&max| self.files.len() > max) {
+            // highest scores first, then restore the relative path order lookups rely on
+            self.files
+                .sort_by(|a, b| b.total_frecency_score.cmp(&a.total_frecency_score));
+            self.files.truncate(max_file_count);
+            self.files
+                .sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        }
+
+        let removed = original_len - self.files.len();
+        if removed > 0 {
+            self.scan_generation = self.scan_generation.wrapping_add(1);
+            self.rebuild_inode_index();
+            self.update_directory_frecency();
+        }
+        removed
     }
 }
 
+fn files_memory_usage(files: &Vec<FileItem>) -> usize {
+    let heap_bytes: usize = files
+        .iter()
+        .map(|file| {
+            file.path.capacity()
+                + file.relative_path.capacity()
+                + file.file_name.capacity()
+                + file.extension.capacity()
+                + file.directory.capacity()
+        })
+        .sum();
+
+    files.capacity() * std::mem::size_of::<FileItem>() + heap_bytes
+}
+
+const CONTENT_PREVIEW_MAX_CHARS: usize = 120;
+const COMMENT_PREFIXES: [&str; 8] = ["//", "#", "--", "/*", "*", ";", "<!--", "\"\"\""];
+
 impl FileItem {
-    fn new(path: PathBuf, base_path: &Path, git_status: Option<Status>) -> Self {
-        let relative_path = pathdiff::diff_paths(&path, base_path)
-            .unwrap_or_else(|| path.clone())
-            .to_string_lossy()
-            .into_owned();
+    /// Builds the item with its status looked up in `git_cache`, outside of a repository it has none
+    fn new_with_git_status(
+        path: PathBuf,
+        base_path: &Path,
+        git_cache: Option<&GitStatusCache>,
+    ) -> Option<Self> {
+        let git_status = git_cache.and_then(|cache| cache.lookup_status(&path));
+        Self::new(path, base_path, git_status)
+    }
+
+    /// None for paths outside of `base_path`, they don't belong in the index
+    pub(crate) fn new(path: PathBuf, base_path: &Path, git_status: Option<Status>) -> Option<Self> {
+        let relative_path = relative_to(&path, base_path)?;
 
         let name = path
             .file_name()
@@ -114,8 +737,15 @@ impl FileItem {
             _ => String::new(),
         };
 
-        let (size, modified) = match std::fs::metadata(&path) {
-            Ok(metadata) => {
+        let is_test_file = is_test_file_name(&name) || is_in_test_directory(&relative_path);
+        let is_config_file = detect_config_file(&name, &extension);
+        let category = categorize(&name, &extension, &directory).name();
+
+        let metadata = std::fs::metadata(&path).ok();
+        let is_hidden = is_hidden_file(&name, metadata.as_ref());
+        let inode = metadata.as_ref().map_or(0, file_inode);
+        let (size, modified) = match metadata {
+            Some(metadata) => {
                 let size = metadata.len();
                 let modified = metadata
                     .modified()
@@ -125,10 +755,11 @@ impl FileItem {
 
                 (size, modified)
             }
-            Err(_) => (0, 0),
+            None => (0, 0),
         };
+        let estimated_lines = estimate_lines_from_size(size, &extension);
 
-        Self {
+        Some(Self {
             path,
             relative_path,
             file_name: name,
@@ -139,9 +770,16 @@ impl FileItem {
             access_frecency_score: 0,
             modification_frecency_score: 0,
             total_frecency_score: 0,
+            parent_directory_frecency: 0,
             git_status,
             is_current_file: false,
-        }
+            is_test_file,
+            is_config_file,
+            is_hidden,
+            inode,
+            category,
+            estimated_lines,
+        })
     }
 
     fn update_frecency_scores(&mut self) {
@@ -156,6 +794,40 @@ impl FileItem {
             }
         }
     }
+
+    /// The relative path shortened to `max_width` characters, see `shorten_path`
+    pub fn display_path(&self, max_width: usize) -> String {
+        shorten_path(&self.relative_path, max_width)
+    }
+
+    /// The relative path without the leading `dir` directory, as shown for files under a `@dir:`
+    /// filter. The full relative path is returned for files outside of `dir`.
+    pub fn relative_to_directory<'a>(&'a self, dir: &str) -> &'a str {
+        strip_path_prefix(&self.relative_path, dir)
+    }
+
+    /// First non-blank, non-comment line of the file, only read for files smaller than `max_bytes`
+    pub fn content_preview(&self, max_bytes: u64) -> Option<String> {
+        if self.size == 0 || self.size > max_bytes {
+            return None;
+        }
+
+        let content = std::fs::read(&self.path).ok()?;
+        if content.contains(&0) {
+            return None; // binary file
+        }
+
+        String::from_utf8_lossy(&content)
+            .lines()
+            .map(str::trim)
+            .find(|line| {
+                !line.is_empty()
+                    && !COMMENT_PREFIXES
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix))
+            })
+            .map(|line| line.chars().take(CONTENT_PREVIEW_MAX_CHARS).collect())
+    }
 }
 
 impl From<&FileItem> for FileKey {
@@ -169,9 +841,27 @@ impl From<&FileItem> for FileKey {
 pub struct FilePicker {
     base_path: PathBuf,
     git_workdir: Option<PathBuf>,
+    /// Searches only take the read lock so concurrent searches never wait on each other, just
+    /// on the short writes of the watcher. Swapping whole snapshots atomically instead would
+    /// clone the file list on every watcher event. `bench_concurrent_fuzzy_search` compares one
+    /// and four readers when it's worth revisiting.
     sync_data: Arc<RwLock<FileSync>>,
     shutdown_signal: Arc<AtomicBool>,
+    /// Stops the current background watcher only, the memory monitor keeps running when the
+    /// watcher is replaced for a new base path
+    watcher_shutdown: Arc<AtomicBool>,
     is_scanning: Arc<AtomicBool>,
+    scan_complete: Arc<(Mutex<()>, Condvar)>,
+    /// Set while file watching is suspended because of memory pressure
+    paused: Arc<AtomicBool>,
+    /// `scan_generation` of the file list searches use, `LIVE_FILES` for the live one
+    viewed_generation: AtomicU64,
+    /// Times the background watcher was restarted after panicking
+    watcher_restart_count: Arc<AtomicU32>,
+    /// Kept apart from `sync_data` so opening the repository never blocks searches
+    repository_cache: RepositoryCache,
+    /// Most recent selections last, at most `SEARCH_HISTORY_CAPACITY`
+    search_history: Mutex<VecDeque<SearchHistoryEntry>>,
     _background_handle: Option<thread::JoinHandle<()>>,
 }
 
@@ -185,44 +875,137 @@ impl std::fmt::Debug for FilePicker {
 }
 
 impl FilePicker {
-    pub fn new(base_path: String) -> Result<Self, Error> {
+    /// Starts watching and scanning `base_path`. The files from `cache_path` (if present and
+    /// valid) are shown while the initial scan is still running.
+    pub fn with_file_cache(base_path: String, cache_path: Option<PathBuf>) -> Result<Self, Error> {
         info!("Initializing FilePicker with base_path: {}", base_path);
         let path = PathBuf::from(&base_path);
-        if !path.exists() {
-            error!("Base path does not exist: {}", base_path);
-            return Err(Error::InvalidPath(path.to_string_lossy().into_owned()));
-        }
+        validate_base_path(&path)?;
 
-        let git_workdir = Repository::discover(&path)
-            .ok()
-            .and_then(|repo| repo.workdir().map(Path::to_path_buf));
+        let git_workdir = discover_git_workdir(&path);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        spawn_memory_monitor(Arc::clone(&paused), Arc::clone(&shutdown));
+
+        let mut picker = Self {
+            base_path: path,
+            git_workdir,
+            sync_data: Arc::new(RwLock::new(FileSync::new())),
+            shutdown_signal: shutdown,
+            watcher_shutdown: Arc::new(AtomicBool::new(false)),
+            is_scanning: Arc::new(AtomicBool::new(false)),
+            scan_complete: Arc::new((Mutex::new(()), Condvar::new())),
+            paused,
+            viewed_generation: AtomicU64::new(LIVE_FILES),
+            watcher_restart_count: Arc::new(AtomicU32::new(0)),
+            repository_cache: RepositoryCache::default(),
+            search_history: Mutex::new(VecDeque::with_capacity(SEARCH_HISTORY_CAPACITY)),
+            _background_handle: None,
+        };
+        picker.start_watcher(cache_path);
+        Ok(picker)
+    }
+
+    /// Spawns the background watcher, which scans the base path first
+    fn start_watcher(&mut self, cache_path: Option<PathBuf>) {
+        DATA_INTEGRITY_WARNING.store(false, Ordering::Relaxed);
+        self._background_handle = Some(spawn_background_watcher(
+            self.base_path.clone(),
+            self.git_workdir.clone(),
+            cache_path,
+            Arc::clone(&self.sync_data),
+            Arc::clone(&self.watcher_shutdown),
+            Arc::clone(&self.is_scanning),
+            Arc::clone(&self.scan_complete),
+            Arc::clone(&self.paused),
+            Arc::clone(&self.watcher_restart_count),
+            self.repository_cache.clone(),
+        ));
+    }
+
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
+    /// Root of the git repository containing the base path, if any
+    pub fn git_workdir(&self) -> Option<&Path> {
+        self.git_workdir.as_deref()
+    }
+
+    fn lock_search_history(&self) -> std::sync::MutexGuard<'_, VecDeque<SearchHistoryEntry>> {
+        // entries are pushed and popped whole, a panicking holder can't leave one half written
+        self.search_history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
 
-        if let Some(ref git_dir) = git_workdir {
-            debug!("Git repository found at: {}", git_dir.display());
-        } else {
-            debug!("No git repository found for path: {}", base_path);
+    /// Appends a selection, dropping the oldest one when full. Returns every entry oldest first
+    /// so the caller can persist them.
+    pub fn record_search_selection(
+        &self,
+        query: String,
+        selected_path: String,
+    ) -> Vec<SearchHistoryEntry> {
+        let mut history = self.lock_search_history();
+        if history.len() == SEARCH_HISTORY_CAPACITY {
+            history.pop_front();
         }
+        history.push_back(SearchHistoryEntry {
+            query,
+            selected_path,
+            timestamp: unix_now(),
+        });
+        history.iter().cloned().collect()
+    }
 
-        let sync_data = Arc::new(RwLock::new(FileSync::new()));
-        let shutdown = Arc::new(AtomicBool::new(false));
-        let scan_signal = Arc::new(AtomicBool::new(false));
+    /// Up to `limit` entries, most recent first
+    pub fn search_history(&self, limit: usize) -> Vec<SearchHistoryEntry> {
+        self.lock_search_history()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Replaces the history with persisted `entries`, oldest first
+    pub fn set_search_history(&self, entries: Vec<SearchHistoryEntry>) {
+        let skip = entries.len().saturating_sub(SEARCH_HISTORY_CAPACITY);
+        *self.lock_search_history() = entries.into_iter().skip(skip).collect();
+    }
+
+    pub fn clear_search_history(&self) {
+        self.lock_search_history().clear();
+    }
 
-        let background_handle = spawn_background_watcher(
-            path.clone(),
-            git_workdir.clone(),
-            Arc::clone(&sync_data),
-            Arc::clone(&shutdown),
-            Arc::clone(&scan_signal),
+    /// Moves the picker to a new root. The current watcher is signaled to shut down, the file
+    /// data and watcher counters start from scratch and only the watcher and its scan are
+    /// started again, the memory monitor keeps running. The search history is left to the
+    /// caller since it belongs to the project.
+    pub fn set_base_path(&mut self, new_base_path: String) -> Result<(), Error> {
+        info!(
+            "Changing FilePicker base_path from {} to {}",
+            self.base_path.display(),
+            new_base_path
         );
+        let path = PathBuf::from(&new_base_path);
+        validate_base_path(&path)?;
 
-        Ok(Self {
-            base_path: path,
-            git_workdir,
-            sync_data,
-            shutdown_signal: shutdown,
-            is_scanning: scan_signal,
-            _background_handle: Some(background_handle),
-        })
+        self.watcher_shutdown.store(true, Ordering::Relaxed);
+        // the previous watcher may still be scanning the old path, it keeps writing to its own
+        // file data and scan state instead of the ones of the new path
+        self.watcher_shutdown = Arc::new(AtomicBool::new(false));
+        self.sync_data = Arc::new(RwLock::new(FileSync::new()));
+        self.is_scanning = Arc::new(AtomicBool::new(false));
+        self.scan_complete = Arc::new((Mutex::new(()), Condvar::new()));
+        self.watcher_restart_count = Arc::new(AtomicU32::new(0));
+        self.repository_cache = RepositoryCache::default();
+        self.viewed_generation.store(LIVE_FILES, Ordering::Relaxed);
+
+        self.git_workdir = discover_git_workdir(&path);
+        self.base_path = path;
+        self.start_watcher(None);
+        Ok(())
     }
 
     pub fn fuzzy_search(
@@ -231,44 +1014,137 @@ impl FilePicker {
         max_results: usize,
         max_threads: usize,
         current_file: Option<&String>,
+        max_typos_override: Option<u16>,
+    ) -> SearchResult {
+        self.search_until(
+            query,
+            SearchMode::Fuzzy,
+            max_results,
+            max_threads,
+            current_file,
+            max_typos_override,
+            None,
+        )
+    }
+
+    /// Searches with any `SearchMode`, `fuzzy_search` is the same as using `SearchMode::Fuzzy`
+    pub fn search(
+        &self,
+        query: &str,
+        mode: SearchMode,
+        max_results: usize,
+        max_threads: usize,
+        current_file: Option<&String>,
+        max_typos_override: Option<u16>,
+    ) -> SearchResult {
+        self.search_until(
+            query,
+            mode,
+            max_results,
+            max_threads,
+            current_file,
+            max_typos_override,
+            None,
+        )
+    }
+
+    /// Same as `fuzzy_search` but stops scoring once `timeout` elapses, returning the results
+    /// from the files scored so far with `is_partial` set
+    pub fn fuzzy_search_timed(
+        &self,
+        query: &str,
+        max_results: usize,
+        max_threads: usize,
+        current_file: Option<&String>,
+        timeout: Duration,
+    ) -> SearchResult {
+        self.search_until(
+            query,
+            SearchMode::Fuzzy,
+            max_results,
+            max_threads,
+            current_file,
+            None,
+            Some(std::time::Instant::now() + timeout),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search_until(
+        &self,
+        query: &str,
+        mode: SearchMode,
+        max_results: usize,
+        max_threads: usize,
+        current_file: Option<&String>,
+        max_typos_override: Option<u16>,
+        deadline: Option<std::time::Instant>,
     ) -> SearchResult {
         let max_threads = max_threads.max(1); // Ensure at least 1 to avoid neo_frizbee division by zero
 
         debug!(
-            "Fuzzy search: query='{}', max_results={}, max_threads={}, current_file={:?}",
-            query, max_results, max_threads, current_file
+            "Search: query='{}', mode={:?}, max_results={}, max_threads={}, current_file={:?}",
+            query, mode, max_results, max_threads, current_file
         );
 
         let time = std::time::Instant::now();
-        let sync_data = self.sync_data.read().unwrap();
-        let total_files = sync_data.files.len();
+        let sync_data = read_sync_data(&self.sync_data);
+        let files = sync_data.snapshot(self.viewed_generation());
+        let files = files.as_ref();
+        let total_files = files.len();
 
-        // small queries with a large number of results can match absolutely everything
-        let max_typos = (query.len() as u16 / 4).clamp(2, 6);
+        let max_typos = max_typos_override.unwrap_or_else(|| default_max_typos(query));
+        let current_file_normalized = current_file.map(|path| normalize_separators(path));
+        let current_file_data = current_file_normalized
+            .as_deref()
+            .map(CurrentFileData::from_path);
         let context = ScoringContext {
             query,
             max_typos,
             max_threads,
             current_file,
+            current_file_data: current_file_data.as_ref(),
+            branch_changed_files: Some(&sync_data.branch_changed_files)
+                .filter(|changed| !changed.is_empty()),
+            branch_change_bonus: BRANCH_CHANGE_BONUS.load(Ordering::Relaxed),
+            config_file_penalty: CONFIG_FILE_PENALTY.load(Ordering::Relaxed),
+            hidden_file_penalty: HIDDEN_FILE_PENALTY.load(Ordering::Relaxed),
+            directory_frecency_weight: f64::from_bits(
+                DIRECTORY_FRECENCY_WEIGHT.load(Ordering::Relaxed),
+            ),
+            filename_similarity_weight: f64::from_bits(
+                FILENAME_SIMILARITY_WEIGHT.load(Ordering::Relaxed),
+            ),
+            frecency_weight_access: FRECENCY_WEIGHT_ACCESS.load(Ordering::Relaxed),
+            frecency_weight_modification: FRECENCY_WEIGHT_MODIFICATION.load(Ordering::Relaxed),
+            early_exit_threshold: EARLY_EXIT_THRESHOLD.load(Ordering::Relaxed),
+            preferred_extensions: Some(&sync_data.preferred_extensions)
+                .filter(|preferred| !preferred.is_empty()),
+            chunk_size: DEFAULT_MATCH_CHUNK_SIZE,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            allow_fallback: true,
         };
 
-        let scored_indices = match_and_score_files(&sync_data.files, &context);
+        let (scored_indices, is_partial) = match deadline {
+            None => (mode.search(files, query, &context), false),
+            Some(deadline) => search_in_chunks(
+                files,
+                mode,
+                query,
+                &context,
+                TIMED_SEARCH_CHUNK_SIZE,
+                deadline,
+            ),
+        };
         let total_matched = scored_indices.len();
+        let (items, scores) = collect_top_results(files, scored_indices, max_results);
 
-        let mut scored_files: Vec<(FileItem, Score)> = scored_indices
-            .into_par_iter()
-            .map(|(idx, score)| (sync_data.files[idx].clone(), score))
-            .collect();
-
-        scored_files.par_sort_unstable_by(|a, b| {
-            b.1.total
-                .cmp(&a.1.total)
-                .then_with(|| b.0.modified.cmp(&a.0.modified))
-        });
-
-        scored_files.truncate(max_results);
-
-        let (items, scores): (Vec<FileItem>, Vec<Score>) = scored_files.into_iter().unzip();
+        if is_partial {
+            warn!(
+                "Fuzzy search for '{}' timed out, returning {} partial matches",
+                query, total_matched
+            );
+        }
 
         debug!(
             "Fuzzy search completed: found {} results for query '{}', total_matched={}, total_files={}, top result {:?}",
@@ -285,38 +1161,208 @@ impl FilePicker {
             scores,
             total_matched,
             total_files,
+            query: context.query.to_string(),
+            is_partial,
+            directory: directory_filter(query),
+        }
+    }
+
+    /// Searches only files that have a path component starting with `query` (case-insensitive),
+    /// matches in components closer to the root rank higher. Same as `SearchMode::Anchored`.
+    pub fn anchored_search(
+        &self,
+        query: &str,
+        max_results: usize,
+        max_threads: usize,
+    ) -> SearchResult {
+        self.search_until(
+            query,
+            SearchMode::Anchored,
+            max_results,
+            max_threads,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Makes searches use the file list from before the previous update that removed files,
+    /// returns false once the oldest kept one is already in use. The list stays the same while
+    /// later updates come in, see `SNAPSHOT_HISTORY_ENABLED`.
+    pub fn navigate_to_previous_snapshot(&self) -> bool {
+        let sync_data = read_sync_data(&self.sync_data);
+        let viewed_generation = self.viewed_generation.load(Ordering::Relaxed);
+        let previous_generation = sync_data
+            .snapshot_history
+            .iter()
+            .rev()
+            .map(|removed| removed.generation)
+            .find(|&generation| generation < viewed_generation);
+
+        previous_generation.is_some_and(|generation| {
+            self.viewed_generation.store(generation, Ordering::Relaxed);
+            true
+        })
+    }
+
+    /// Makes searches use the live file list again
+    pub fn navigate_to_current(&self) {
+        self.viewed_generation.store(LIVE_FILES, Ordering::Relaxed);
+    }
+
+    fn viewed_generation(&self) -> Option<u64> {
+        Some(self.viewed_generation.load(Ordering::Relaxed)).filter(|&g| g != LIVE_FILES)
+    }
+
+    pub fn get_file(&self, relative_path: &str) -> Option<FileItem> {
+        let sync_data = read_sync_data(&self.sync_data);
+        let index = sync_data.find_file_index(relative_path).ok()?;
+        sync_data.files.get(index).cloned()
+    }
+
+    /// Diff of a file (relative to the base path or absolute) against HEAD, `None` outside of git
+    pub fn get_file_diff(&self, file_path: &str, context_lines: usize) -> Option<String> {
+        let git_workdir = self.git_workdir.as_ref()?;
+        diff_against_head(git_workdir, &self.base_path.join(file_path), context_lines)
+    }
+
+    /// Stash count as of the last full scan, 0 outside of git
+    pub fn stash_count(&self) -> usize {
+        read_sync_data(&self.sync_data).stash_count
+    }
+
+    /// Stashes read from the repository right away, most recent first
+    pub fn get_stash_list(&self) -> Vec<StashEntry> {
+        list_stashes(self.git_workdir.as_deref())
+    }
+
+    pub fn save_file_cache(&self, path: &Path) -> Result<(), Error> {
+        read_sync_data(&self.sync_data).save_to_disk(path, &self.base_path)
+    }
+
+    /// Replaces the indexed files with the cached ones, returns the number of loaded files. The
+    /// cache is only used until a scan has indexed the current files, a finished scan is never
+    /// overwritten with outdated ones.
+    pub fn load_file_cache(&self, path: &Path) -> Result<usize, Error> {
+        let loaded = FileSync::load_from_disk(path, &self.base_path)?;
+        let mut sync_data = self
+            .sync_data
+            .write()
+            .map_err(|_| Error::AcquireItemLock("load_file_cache"))?;
+        if sync_data.scan_generation != 0 && !self.is_scan_active() {
+            debug!("Not loading the file cache, the files are already scanned");
+            return Ok(0);
         }
+        sync_data.update_files(loaded.files, None);
+        Ok(sync_data.files.len())
+    }
+
+    pub fn has_git_changes(&self) -> bool {
+        read_sync_data(&self.sync_data)
+            .git_status_cache
+            .as_ref()
+            .is_some_and(GitStatusCache::has_any_changes)
+    }
+
+    pub fn git_changed_file_count(&self) -> usize {
+        read_sync_data(&self.sync_data)
+            .git_status_cache
+            .as_ref()
+            .map_or(0, GitStatusCache::changed_file_count)
+    }
+
+    pub fn get_directory_stats(&self) -> HashMap<String, DirStats> {
+        read_sync_data(&self.sync_data).stats_by_directory()
+    }
+
+    pub fn get_slow_directories(&self) -> Vec<SlowDirectory> {
+        read_sync_data(&self.sync_data).slow_directories.clone()
+    }
+
+    pub fn get_detected_language(&self) -> Option<String> {
+        read_sync_data(&self.sync_data)
+            .detected_language
+            .map(str::to_string)
+    }
+
+    /// Replaces the detected preferred extensions, later scans keep the configured ones
+    pub fn set_preferred_extensions(&self, extensions: HashSet<String>) -> Result<(), Error> {
+        let mut sync_data = self
+            .sync_data
+            .write()
+            .map_err(|_| Error::AcquireItemLock("set_preferred_extensions"))?;
+        sync_data.preferred_extensions = extensions;
+        sync_data.preferred_extensions_overridden = true;
+        Ok(())
+    }
+
+    pub fn compact_files(
+        &self,
+        min_total_frecency_score: i64,
+        max_file_count: Option<usize>,
+    ) -> Result<usize, Error> {
+        let mut sync_data = self
+            .sync_data
+            .write()
+            .map_err(|_| Error::AcquireItemLock("compact_files"))?;
+        Ok(sync_data.compact_snapshot(min_total_frecency_score, max_file_count))
+    }
+
+    pub fn get_memory_usage_bytes(&self) -> usize {
+        read_sync_data(&self.sync_data).approximate_memory_usage()
+    }
+
+    /// Files the last scan visited but left out of the index: unreadable entries, git files
+    /// and filtered extensions. Entries pruned by ignore files are never visited and not
+    /// counted.
+    pub fn get_skipped_file_count(&self) -> usize {
+        read_sync_data(&self.sync_data).skipped_file_count
     }
 
     pub fn get_cached_files(&self) -> Vec<FileItem> {
-        self.sync_data.read().unwrap().files.clone()
+        read_sync_data(&self.sync_data).files.clone()
+    }
+
+    pub fn get_debug_info(&self) -> DebugInfo {
+        let sync_data = read_sync_data(&self.sync_data);
+        DebugInfo {
+            base_path: self.base_path.clone(),
+            git_workdir: self.git_workdir.clone(),
+            file_count: sync_data.files.len(),
+            skipped_file_count: sync_data.skipped_file_count,
+            scan_generation: sync_data.scan_generation,
+            is_scanning: self.is_scanning.load(Ordering::Relaxed),
+            is_watching_paused: self.is_watching_paused(),
+            viewed_generation: self.viewed_generation(),
+            last_scan_duration: sync_data.last_scan_duration,
+        }
     }
 
+    /// The flag is read before the files: it is only cleared after a scan stored its files, so a
+    /// finished scan is never reported together with the files from before it
     pub fn get_scan_progress(&self) -> ScanProgress {
-        let sync_data = self.sync_data.read().unwrap();
-        let is_scanning = self.is_scanning.load(Ordering::Relaxed);
+        let is_scanning = self.is_scan_active();
+        let sync_data = read_sync_data(&self.sync_data);
         ScanProgress {
             total_files: sync_data.files.len(),
             scanned_files: sync_data.files.len(),
+            skipped_files: sync_data.skipped_file_count,
             is_scanning,
         }
     }
 
     pub fn refresh_git_status(&self) -> Vec<FileItem> {
         let sync_data: &Arc<RwLock<FileSync>> = &self.sync_data;
-        let git_workdir = self.git_workdir.as_deref();
-        let new_git_status_cache = GitStatusCache::read_git_status(git_workdir);
+        let new_git_status_cache = self.git_workdir.as_deref().and_then(|git_workdir| {
+            self.repository_cache
+                .with_repository(git_workdir, |repo| {
+                    GitStatusCache::read_repository_status(repo, git_workdir)
+                })
+                .flatten()
+        });
 
         if let Ok(mut sync_data_write) = sync_data.write() {
-            sync_data_write.git_status_cache = new_git_status_cache.clone();
-
-            for file in &mut sync_data_write.files {
-                file.git_status = new_git_status_cache
-                    .as_ref()
-                    .and_then(|git| git.lookup_status(&file.path));
-
-                file.update_frecency_scores();
-            }
+            sync_data_write.apply_git_status(new_git_status_cache);
         }
 
         self.get_cached_files()
@@ -330,38 +1376,156 @@ impl FilePicker {
 
         info!("is_scanning = TRUE (manual rescan starting)");
         self.is_scanning.store(true, Ordering::Relaxed);
+        // picks up repositories or worktrees created and removed since it was opened
+        self.repository_cache.invalidate();
 
         let base_path = self.base_path.clone();
         let git_workdir = self.git_workdir.clone();
         let sync_data = Arc::clone(&self.sync_data);
         let scan_signal = Arc::clone(&self.is_scanning);
+        let scan_complete = Arc::clone(&self.scan_complete);
 
         thread::spawn(move || {
             debug!("Background scan thread started");
-            if let Ok((files, git_cache)) = scan_filesystem(&base_path, git_workdir.as_ref()) {
-                info!("Filesystem scan completed: found {} files", files.len());
-                if let Ok(mut data) = sync_data.write() {
-                    data.update_files(files, git_cache);
-                    debug!("File cache updated successfully");
-                }
-            } else {
-                warn!("Filesystem scan failed");
-            }
-
-            scan_signal.store(false, Ordering::Relaxed);
+            rescan_filesystem(
+                &base_path,
+                git_workdir.as_ref(),
+                &sync_data,
+                &scan_signal,
+                &scan_complete,
+            );
             info!("is_scanning = FALSE (manual rescan completed)");
         });
 
         Ok(())
     }
 
+    /// Stays true until the scanned files are stored in the index, not just until the walk ends
     pub fn is_scan_active(&self) -> bool {
-        self.is_scanning.load(Ordering::Relaxed)
+        self.is_scanning.load(Ordering::Acquire)
+    }
+
+    /// Handles that allow waiting for the scan to finish without holding a reference to the picker
+    pub fn scan_waiter(&self) -> (Arc<AtomicBool>, Arc<(Mutex<()>, Condvar)>) {
+        (
+            Arc::clone(&self.is_scanning),
+            Arc::clone(&self.scan_complete),
+        )
+    }
+
+    pub fn is_watching_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn get_watcher_status(&self) -> WatcherStatus {
+        WatcherStatus {
+            is_running: self
+                ._background_handle
+                .as_ref()
+                .is_some_and(|handle| !handle.is_finished()),
+            is_paused: self.is_watching_paused(),
+            data_integrity_warning: DATA_INTEGRITY_WARNING.load(Ordering::Relaxed),
+            restart_count: self.watcher_restart_count.load(Ordering::Relaxed),
+        }
     }
 
     pub fn stop_background_monitor(&self) {
         self.shutdown_signal.store(true, Ordering::Relaxed);
+        self.watcher_shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Number of files scored between deadline checks of a timed search
+const TIMED_SEARCH_CHUNK_SIZE: usize = 10_000;
+
+/// Searches `files` in chunks of `chunk_size`, checking `deadline` between them since the
+/// matcher can not be interrupted. Returns the matches and whether the deadline cut the search
+/// short. The fallbacks for queries without strict matches are decided for all files at once,
+/// so a search that completes returns the same results as an untimed one.
+fn search_in_chunks(
+    files: &[FileItem],
+    mode: SearchMode,
+    query: &str,
+    context: &ScoringContext,
+    chunk_size: usize,
+    deadline: std::time::Instant,
+) -> (Vec<(usize, Score)>, bool) {
+    let chunk_context = ScoringContext {
+        allow_fallback: false,
+        ..context.clone()
+    };
+    let mut scored_indices = Vec::new();
+    for (chunk_index, chunk) in files.chunks(chunk_size).enumerate() {
+        if std::time::Instant::now() >= deadline {
+            return (scored_indices, true);
+        }
+
+        let offset = chunk_index * chunk_size;
+        scored_indices.extend(
+            mode.search(chunk, query, &chunk_context)
+                .into_iter()
+                .map(|(idx, score)| (idx + offset, score)),
+        );
+    }
+
+    if scored_indices.is_empty() && context.allow_fallback {
+        scored_indices = mode.search(files, query, context);
     }
+    (scored_indices, false)
+}
+
+/// Sorts scored files by score and keeps the best ones. Ties go to the shorter path, which
+/// tends to be the more central file, then to the most recently modified one. Remaining ties
+/// keep the input order so equal scores, e.g. an empty query without any frecency data, list
+/// the same way on every call.
+fn collect_top_results(
+    files: &[FileItem],
+    mut scored_indices: Vec<(usize, Score)>,
+    max_results: usize,
+) -> (Vec<FileItem>, Vec<Score>) {
+    scored_indices.par_sort_unstable_by(|a, b| {
+        b.1.total
+            .cmp(&a.1.total)
+            .then_with(|| {
+                files[a.0]
+                    .relative_path
+                    .len()
+                    .cmp(&files[b.0].relative_path.len())
+            })
+            .then_with(|| files[b.0].modified.cmp(&files[a.0].modified))
+            .then_with(|| a.0.cmp(&b.0))
+    });
+
+    scored_indices.truncate(max_results);
+    scored_indices
+        .into_iter()
+        .map(|(idx, score)| (files[idx].clone(), score))
+        .unzip()
+}
+
+#[derive(Debug, Clone)]
+pub struct WatcherStatus {
+    pub is_running: bool,
+    pub is_paused: bool,
+    /// The file data may be partially updated after a panic in the watcher
+    pub data_integrity_warning: bool,
+    /// Times the watcher was restarted after panicking
+    pub restart_count: u32,
+}
+
+/// Read-only snapshot of the picker state for bug reports
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    pub base_path: PathBuf,
+    pub git_workdir: Option<PathBuf>,
+    pub file_count: usize,
+    pub skipped_file_count: usize,
+    pub scan_generation: u64,
+    pub is_scanning: bool,
+    pub is_watching_paused: bool,
+    /// `scan_generation` of the previous file list searches use, `None` for the live one
+    pub viewed_generation: Option<u64>,
+    pub last_scan_duration: Option<Duration>,
 }
 
 #[allow(unused)]
@@ -369,76 +1533,502 @@ impl FilePicker {
 pub struct ScanProgress {
     pub total_files: usize,
     pub scanned_files: usize,
+    pub skipped_files: usize,
     pub is_scanning: bool,
 }
 
+/// Number of selections kept by `FilePicker::record_search_selection`
+pub const SEARCH_HISTORY_CAPACITY: usize = 100;
+
+/// How long file system events are collected before the index is updated
+pub const WATCHER_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Root of the git repository containing `path`, if any
+fn discover_git_workdir(path: &Path) -> Option<PathBuf> {
+    let git_workdir = Repository::discover(path)
+        .ok()
+        .and_then(|repo| repo.workdir().map(Path::to_path_buf));
+    match &git_workdir {
+        Some(git_dir) => debug!("Git repository found at: {}", git_dir.display()),
+        None => debug!("No git repository found for path: {}", path.display()),
+    }
+    git_workdir
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_background_watcher(
     base_path: PathBuf,
     git_workdir: Option<PathBuf>,
+    cache_path: Option<PathBuf>,
     sync_data: Arc<RwLock<FileSync>>,
     shutdown: Arc<AtomicBool>,
     scan_signal: Arc<AtomicBool>,
+    scan_complete: Arc<(Mutex<()>, Condvar)>,
+    paused: Arc<AtomicBool>,
+    restart_count: Arc<AtomicU32>,
+    repository_cache: RepositoryCache,
 ) -> thread::JoinHandle<()> {
+    // set before spawning so waiting right after construction can't miss the initial scan
+    scan_signal.store(true, Ordering::Relaxed);
+    let mut cache_path = cache_path;
     thread::spawn(move || {
-        scan_signal.store(true, Ordering::Relaxed);
-        info!("starting background watcher thread");
+        supervise_watcher(&shutdown, &restart_count, WATCHER_RESTART_DELAY, || {
+            // a restarted watcher rescans since it missed the events of the crashed one
+            scan_signal.store(true, Ordering::Relaxed);
+            let base_path = base_path.clone();
+            let git_workdir = git_workdir.clone();
+            let cache_path = cache_path.take();
+            let sync_data = Arc::clone(&sync_data);
+            let shutdown = Arc::clone(&shutdown);
+            let scan_signal = Arc::clone(&scan_signal);
+            let scan_complete = Arc::clone(&scan_complete);
+            let paused = Arc::clone(&paused);
+            let repository_cache = repository_cache.clone();
+            thread::spawn(move || {
+                run_background_watcher(
+                    base_path,
+                    git_workdir,
+                    cache_path,
+                    sync_data,
+                    shutdown,
+                    scan_signal,
+                    scan_complete,
+                    paused,
+                    repository_cache,
+                )
+            })
+        });
+    })
+}
 
-        match scan_filesystem(&base_path, git_workdir.as_ref()) {
-            Ok((files, git_cache)) => {
-                info!(
-                    "Initial parallel filesystem scan completed: found {} files",
-                    files.len()
-                );
-                if let Ok(mut data) = sync_data.write() {
-                    data.update_files(files, git_cache);
-                    debug!("Initial file cache updated successfully");
-                }
-            }
-            Err(e) => {
-                error!("Failed to scan filesystem: {:?}", e);
+/// Delay before a panicked watcher is started again, doubled after every restart
+const WATCHER_RESTART_DELAY: Duration = Duration::from_secs(1);
+const WATCHER_MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+/// Restarts after which a watcher that keeps panicking is given up on
+const WATCHER_RESTART_LIMIT: u32 = 5;
+
+/// Joins the watcher thread returned by `spawn_watcher` and spawns a new one whenever it
+/// panicked, until it returns normally, `shutdown` is set or it was restarted
+/// `WATCHER_RESTART_LIMIT` times. The delay before a restart starts at `restart_delay` and
+/// doubles every time.
+fn supervise_watcher(
+    shutdown: &AtomicBool,
+    restart_count: &AtomicU32,
+    restart_delay: Duration,
+    mut spawn_watcher: impl FnMut() -> thread::JoinHandle<()>,
+) {
+    let mut restarts = 0;
+    let mut restart_delay = restart_delay;
+    while spawn_watcher().join().is_err() {
+        if restarts == WATCHER_RESTART_LIMIT {
+            error!(
+                "Background watcher panicked {} times in a row, giving up on watching files",
+                restarts + 1
+            );
+            return;
+        }
+        error!(
+            "Background watcher panicked, restarting it in {:?}",
+            restart_delay
+        );
+        thread::sleep(restart_delay);
+        if shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        restarts += 1;
+        restart_count.fetch_add(1, Ordering::Relaxed);
+        restart_delay = (restart_delay * 2).min(WATCHER_MAX_RESTART_DELAY);
+    }
+}
+
+/// Runs a debouncer callback. A panic would silently end notify's event thread, so it is caught
+/// and reported through `panicked` for the watcher thread to restart the watcher.
+fn run_watcher_callback(panicked: &AtomicBool, callback: impl FnOnce()) {
+    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(callback)).is_err() {
+        error!("File watcher callback panicked");
+        panicked.store(true, Ordering::Relaxed);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_background_watcher(
+    base_path: PathBuf,
+    git_workdir: Option<PathBuf>,
+    cache_path: Option<PathBuf>,
+    sync_data: Arc<RwLock<FileSync>>,
+    shutdown: Arc<AtomicBool>,
+    scan_signal: Arc<AtomicBool>,
+    scan_complete: Arc<(Mutex<()>, Condvar)>,
+    paused: Arc<AtomicBool>,
+    repository_cache: RepositoryCache,
+) {
+    info!("starting background watcher thread");
+
+    if let Some(cache_path) = cache_path.filter(|path| path.exists()) {
+        match FileSync::load_from_disk(&cache_path, &base_path) {
+            Ok(cached) => {
+                let mut data = write_sync_data(&sync_data);
+                data.update_files(cached.files, None);
             }
+            Err(e) => warn!("Failed to load file cache, doing a full scan: {:?}", e),
         }
+    }
+
+    match scan_filesystem(&base_path, git_workdir.as_ref()) {
+        Ok(scan) => {
+            info!(
+                "Initial parallel filesystem scan completed: found {} files",
+                scan.files.len()
+            );
+            let mut data = write_sync_data(&sync_data);
+            let diff = data.update_files(scan.files, scan.git_cache);
+            debug!(
+                "Scan added {} and removed {} files compared to the cached list",
+                diff.added, diff.removed
+            );
+            data.slow_directories = scan.slow_directories;
+            data.skipped_file_count = scan.skipped_file_count;
+            data.branch_changed_files = scan.branch_changed_files;
+            data.stash_count = scan.stash_count;
+            data.last_scan_duration = Some(scan.duration);
+            debug!("Initial file cache updated successfully");
+        }
+        Err(e) => {
+            error!("Failed to scan filesystem: {:?}", e);
+        }
+    }
 
-        scan_signal.store(false, Ordering::Relaxed);
-        error!("is_scanning = FALSE (initial scan completed)");
+    finish_scan(&scan_signal, &scan_complete);
+    error!("is_scanning = FALSE (initial scan completed)");
 
-        let mut debouncer = match new_debouncer(Duration::from_millis(500), None, {
-            let sync_data = Arc::clone(&sync_data);
-            let base_path = base_path.clone();
-            let git_workdir = git_workdir.clone();
+    let callback_panicked = Arc::new(AtomicBool::new(false));
+    // events are dropped while paused, the index is rescanned once watching resumes instead
+    let missed_events = Arc::new(AtomicBool::new(false));
+    let mut debouncer = match new_debouncer(WATCHER_DEBOUNCE, None, {
+        let sync_data = Arc::clone(&sync_data);
+        let base_path = base_path.clone();
+        let git_workdir = git_workdir.clone();
+        let paused = Arc::clone(&paused);
+        let repository_cache = repository_cache.clone();
+        let callback_panicked = Arc::clone(&callback_panicked);
+        let missed_events = Arc::clone(&missed_events);
+        let mut recent_events = VecDeque::with_capacity(RECENT_EVENTS_CAPACITY);
 
-            move |result: DebounceEventResult| match result {
+        move |result: DebounceEventResult| {
+            run_watcher_callback(&callback_panicked, || match result {
+                Ok(_) if paused.load(Ordering::Relaxed) => {
+                    debug!("File watching paused, dropping debounced events");
+                    missed_events.store(true, Ordering::Relaxed);
+                }
                 Ok(events) => {
-                    handle_debounced_events(events, &sync_data, &base_path, &git_workdir);
+                    let events = coalesce_save_renames(events, &mut recent_events, |path| {
+                        relative_to(path, &base_path).is_some_and(|relative_path| {
+                            read_sync_data(&sync_data).contains_path(&relative_path)
+                        })
+                    });
+                    handle_debounced_events(
+                        events,
+                        &sync_data,
+                        &base_path,
+                        &git_workdir,
+                        &repository_cache,
+                    );
                 }
                 Err(errors) => {
                     error!("File watcher errors: {:?}", errors);
                 }
-            }
-        }) {
-            Ok(debouncer) => debouncer,
-            Err(e) => {
-                error!("Failed to create debouncer: {:?}", e);
-                return;
-            }
-        };
-
-        if let Err(e) = debouncer.watch(&base_path, RecursiveMode::Recursive) {
-            error!("Failed to start watching: {:?}", e);
+            })
+        }
+    }) {
+        Ok(debouncer) => debouncer,
+        Err(e) => {
+            error!("Failed to create debouncer: {:?}", e);
             return;
         }
+    };
 
-        while !shutdown.load(Ordering::Relaxed) {
-            thread::sleep(Duration::from_millis(100));
+    if let Err(e) = debouncer.watch(&base_path, RecursiveMode::Recursive) {
+        error!("Failed to start watching: {:?}", e);
+        return;
+    }
+
+    let _git_index_debouncer = git_workdir.as_ref().and_then(|git_workdir| {
+        watch_git_index(
+            git_workdir,
+            &sync_data,
+            &paused,
+            &repository_cache,
+            &callback_panicked,
+            &missed_events,
+        )
+    });
+
+    while !shutdown.load(Ordering::Relaxed) {
+        // hands the restart over to `supervise_watcher`
+        if callback_panicked.load(Ordering::Relaxed) {
+            panic!("File watcher callback panicked");
+        }
+        // a manual rescan that is already running picks the missed changes up as well
+        if !paused.load(Ordering::Relaxed)
+            && missed_events.swap(false, Ordering::Relaxed)
+            && scan_signal
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            info!("File watching resumed, rescanning for changes missed while paused");
+            rescan_filesystem(
+                &base_path,
+                git_workdir.as_ref(),
+                &sync_data,
+                &scan_signal,
+                &scan_complete,
+            );
         }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Scans `base_path` again and replaces the indexed files, the caller sets `scan_signal`
+fn rescan_filesystem(
+    base_path: &Path,
+    git_workdir: Option<&PathBuf>,
+    sync_data: &RwLock<FileSync>,
+    scan_signal: &AtomicBool,
+    scan_complete: &(Mutex<()>, Condvar),
+) {
+    if let Ok(scan) = scan_filesystem(base_path, git_workdir) {
+        info!(
+            "Filesystem scan completed: found {} files",
+            scan.files.len()
+        );
+        if let Ok(mut data) = sync_data.write() {
+            let diff = data.update_files(scan.files, scan.git_cache);
+            info!(
+                "Rescan added {} and removed {} files",
+                diff.added, diff.removed
+            );
+            data.slow_directories = scan.slow_directories;
+            data.skipped_file_count = scan.skipped_file_count;
+            data.branch_changed_files = scan.branch_changed_files;
+            data.stash_count = scan.stash_count;
+            data.last_scan_duration = Some(scan.duration);
+            debug!("File cache updated successfully");
+        }
+    } else {
+        warn!("Filesystem scan failed");
+    }
+
+    finish_scan(scan_signal, scan_complete);
+}
+
+/// Write access to the file data for the watcher. A writer that panicked poisons the lock,
+/// which would otherwise make the watcher silently drop every later event, so the possibly
+/// partially updated data is recovered and the poison cleared instead.
+fn write_sync_data(sync_data: &RwLock<FileSync>) -> RwLockWriteGuard<'_, FileSync> {
+    sync_data.write().unwrap_or_else(|poisoned| {
+        error!("File data lock was poisoned, continuing with possibly partial data");
+        DATA_INTEGRITY_WARNING.store(true, Ordering::Relaxed);
+        sync_data.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Read access counterpart of `write_sync_data`
+fn read_sync_data(sync_data: &RwLock<FileSync>) -> RwLockReadGuard<'_, FileSync> {
+    sync_data.read().unwrap_or_else(|poisoned| {
+        error!("File data lock was poisoned, continuing with possibly partial data");
+        DATA_INTEGRITY_WARNING.store(true, Ordering::Relaxed);
+        sync_data.clear_poison();
+        poisoned.into_inner()
     })
 }
 
+const GIT_INDEX_FILES: [&str; 2] = ["index", "COMMIT_EDITMSG"];
+
+/// Refreshes the git status of all files whenever git rewrites its index, staging, restoring
+/// and committing don't touch the working tree so the main watcher never sees them
+fn watch_git_index(
+    git_workdir: &Path,
+    sync_data: &Arc<RwLock<FileSync>>,
+    paused: &Arc<AtomicBool>,
+    repository_cache: &RepositoryCache,
+    callback_panicked: &Arc<AtomicBool>,
+    missed_events: &Arc<AtomicBool>,
+) -> Option<Debouncer<RecommendedWatcher, RecommendedCache>> {
+    let git_dir =
+        repository_cache.with_repository(git_workdir, |repo| repo.path().to_path_buf())?;
+
+    let mut debouncer = new_debouncer(WATCHER_DEBOUNCE, None, {
+        let sync_data = Arc::clone(sync_data);
+        let paused = Arc::clone(paused);
+        let git_workdir = git_workdir.to_path_buf();
+        let repository_cache = repository_cache.clone();
+        let callback_panicked = Arc::clone(callback_panicked);
+        let missed_events = Arc::clone(missed_events);
+
+        move |result: DebounceEventResult| {
+            run_watcher_callback(&callback_panicked, || {
+                let Ok(events) = result else {
+                    return;
+                };
+
+                let index_changed = events.iter().flat_map(|event| &event.paths).any(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| GIT_INDEX_FILES.contains(&name))
+                });
+                if !index_changed {
+                    return;
+                }
+                if paused.load(Ordering::Relaxed) {
+                    missed_events.store(true, Ordering::Relaxed);
+                    return;
+                }
+
+                debug!("Git index changed, refreshing git status");
+                let git_status_cache = repository_cache
+                    .with_repository(&git_workdir, |repo| {
+                        GitStatusCache::read_repository_status(repo, &git_workdir)
+                    })
+                    .flatten();
+                let mut sync_write = write_sync_data(&sync_data);
+                sync_write.apply_git_status(git_status_cache);
+            })
+        }
+    })
+    .map_err(|e| error!("Failed to create git index debouncer: {:?}", e))
+    .ok()?;
+
+    debouncer
+        .watch(&git_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| {
+            error!(
+                "Failed to watch git directory {}: {:?}",
+                git_dir.display(),
+                e
+            )
+        })
+        .ok()?;
+
+    Some(debouncer)
+}
+
+const RECENT_EVENTS_CAPACITY: usize = 100;
+const SAVE_RENAME_WINDOW: Duration = Duration::from_secs(2);
+
+/// Collapses the event chain editors produce when saving through a temporary file
+/// (create tmp, remove original, create original, remove tmp) into a single modify of the
+/// original. `recent_events` remembers paths across batches since a chain may be split.
+fn coalesce_save_renames(
+    events: Vec<DebouncedEvent>,
+    recent_events: &mut VecDeque<(PathBuf, EventKind, std::time::Instant)>,
+    is_indexed: impl Fn(&Path) -> bool,
+) -> Vec<DebouncedEvent> {
+    let now = std::time::Instant::now();
+    for event in &events {
+        for path in &event.paths {
+            if recent_events.len() == RECENT_EVENTS_CAPACITY {
+                recent_events.pop_front();
+            }
+            recent_events.push_back((path.clone(), event.kind, now));
+        }
+    }
+
+    let created_recently = |path: &PathBuf| {
+        recent_events.iter().any(|(recent_path, kind, time)| {
+            recent_path == path
+                && kind.is_create()
+                && now.duration_since(*time) <= SAVE_RENAME_WINDOW
+        })
+    };
+
+    let mut modified = HashSet::new();
+    let mut coalesced = Vec::with_capacity(events.len());
+    for mut event in events {
+        let [path] = event.paths.as_slice() else {
+            coalesced.push(event);
+            continue;
+        };
+
+        let replaced = match event.kind {
+            EventKind::Create(_) if !path.exists() => continue, // temporary file already gone
+            EventKind::Create(_) => is_indexed(path),
+            EventKind::Remove(_) if path.exists() => true, // recreated by the rename
+            EventKind::Remove(_) if !is_indexed(path) && created_recently(path) => continue,
+            _ => false,
+        };
+
+        if replaced {
+            if !modified.insert(path.clone()) {
+                continue;
+            }
+            event.event.kind = EventKind::Modify(ModifyKind::Any);
+        }
+        coalesced.push(event);
+    }
+
+    coalesced
+}
+
+/// Clears the scanning flag and wakes up everyone blocked in `wait_for_scan`
+fn finish_scan(scan_signal: &AtomicBool, scan_complete: &(Mutex<()>, Condvar)) {
+    let (lock, condvar) = scan_complete;
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    // pairs with the acquire in `is_scan_active` so the stored files are visible once it is false
+    scan_signal.store(false, Ordering::Release);
+    condvar.notify_all();
+}
+
+/// Blocks until the scanning flag is cleared or the timeout elapses, returns whether the scan finished
+pub fn wait_for_scan(
+    scan_signal: &AtomicBool,
+    scan_complete: &(Mutex<()>, Condvar),
+    timeout: Duration,
+) -> bool {
+    let start_time = std::time::Instant::now();
+    let (lock, condvar) = scan_complete;
+    let mut guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    while scan_signal.load(Ordering::Relaxed) {
+        let Some(remaining) = timeout.checked_sub(start_time.elapsed()) else {
+            break;
+        };
+
+        // wake up periodically in case of a missed notification
+        guard = condvar
+            .wait_timeout(guard, remaining.min(Duration::from_millis(50)))
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+
+    !scan_signal.load(Ordering::Relaxed)
+}
+
+/// `wait_for_scan` for the picker in `file_picker`. The lock is released before blocking so
+/// rescans and reinitialization are not stalled by the waiter.
+pub fn wait_for_picker_scan(
+    file_picker: &RwLock<Option<FilePicker>>,
+    timeout: Duration,
+) -> Result<bool, Error> {
+    let (scan_signal, scan_complete) = {
+        let file_picker = file_picker
+            .read()
+            .map_err(|_| Error::AcquireItemLock("wait_for_initial_scan"))?;
+        let picker = file_picker
+            .as_ref()
+            .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+        picker.scan_waiter()
+    };
+
+    Ok(wait_for_scan(&scan_signal, &scan_complete, timeout))
+}
+
 fn handle_debounced_events(
     events: Vec<DebouncedEvent>,
     sync_data: &Arc<RwLock<FileSync>>,
     base_path: &Path,
     git_workdir: &Option<PathBuf>,
+    repository_cache: &RepositoryCache,
 ) {
     let mut affected_paths = Vec::new();
     for event in events {
@@ -446,21 +2036,44 @@ fn handle_debounced_events(
             .paths
             .iter()
             .filter_map(|path| {
-                let relative_path = pathdiff::diff_paths(path, base_path)?;
-
-                let Ok(sync_read) = sync_data.read() else {
+                // some backends report paths outside of the watched directory, e.g. for mounts
+                let Some(relative_path) = relative_within(path, base_path) else {
+                    trace!(
+                        "Skipping event for path outside of base path: {}",
+                        path.display()
+                    );
                     return None;
                 };
 
-                let relative_str = relative_path.to_string_lossy();
-
-                if sync_read.contains_path(&relative_str) {
+                // released before the ignore checks below wait for the repository
+                let (indexed, indexed_directory) = {
+                    let sync_read = read_sync_data(sync_data);
+                    (
+                        sync_read.contains_path(&relative_path),
+                        sync_read.contains_prefix(&relative_path),
+                    )
+                };
+                if indexed {
                     return Some(path.clone());
                 }
 
                 match event.event.kind {
+                    // the directory is already gone, so the only way to know it was one is the index
+                    EventKind::Remove(_) if indexed_directory => Some(path.clone()),
+                    // a new file may be the first one of its directory, so only the ignore
+                    // rules decide whether it is indexed
                     EventKind::Create(_) => {
-                        if should_add_new_file(path, git_workdir.as_ref()) {
+                        if should_add_new_file(
+                            path,
+                            base_path,
+                            git_workdir.as_ref(),
+                            repository_cache,
+                        ) || should_add_new_directory(
+                            path,
+                            base_path,
+                            git_workdir.as_ref(),
+                            repository_cache,
+                        ) {
                             Some(path.clone())
                         } else {
                             None
@@ -478,7 +2091,13 @@ fn handle_debounced_events(
         debug!(?event, "File watcher event");
         match event.event.kind {
             EventKind::Create(_) => {
-                handle_create_events(&relevant_paths, sync_data, base_path, git_workdir.as_ref());
+                handle_create_events(
+                    &relevant_paths,
+                    sync_data,
+                    base_path,
+                    git_workdir.as_ref(),
+                    repository_cache,
+                );
                 affected_paths.extend(relevant_paths);
             }
             EventKind::Modify(_) => {
@@ -494,73 +2113,277 @@ fn handle_debounced_events(
     }
 
     if !affected_paths.is_empty() {
-        update_git_status_for_paths(sync_data, git_workdir, base_path, &affected_paths);
+        update_git_status_for_paths(
+            sync_data,
+            git_workdir,
+            repository_cache,
+            base_path,
+            &affected_paths,
+        );
     }
 }
 
-fn should_add_new_file(path: &Path, git_workdir: Option<&PathBuf>) -> bool {
-    if is_git_file(path) {
+fn should_add_new_file(
+    path: &Path,
+    base_path: &Path,
+    git_workdir: Option<&PathBuf>,
+    repository_cache: &RepositoryCache,
+) -> bool {
+    if is_git_file(path, base_path) || is_platform_artifact(path) {
         return false;
     }
 
-    if !path.is_file() {
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    if !is_extension_allowed(&extension) {
         return false;
     }
 
-    if let Some(git_workdir) = git_workdir {
-        if let Ok(repo) = Repository::open(git_workdir) {
-            if repo.is_path_ignored(path).unwrap_or(false) {
-                return false;
-            }
-        }
+    if !path.is_file() {
+        return false;
     }
 
-    true
+    !git_workdir.is_some_and(|git_workdir| is_git_ignored(repository_cache, git_workdir, path))
 }
 
-fn handle_create_events(
-    paths: &[PathBuf],
-    sync_data: &Arc<RwLock<FileSync>>,
+/// Directories are indexed with all the files in them, see `collect_directory_files`
+fn should_add_new_directory(
+    path: &Path,
     base_path: &Path,
     git_workdir: Option<&PathBuf>,
-) {
-    let repo = git_workdir.as_ref().and_then(|p| Repository::open(p).ok());
-    if let Ok(mut sync_write) = sync_data.write() {
-        for path in paths {
-            if repo
-                .as_ref()
-                .is_some_and(|repo| repo.is_path_ignored(path).unwrap_or(false))
-            {
-                debug!("Ignoring file {} due to gitignore rules", path.display());
-                continue;
-            }
-
-            let mut file_item = FileItem::new(path.clone(), base_path, None);
-            file_item.update_frecency_scores();
-            sync_write.insert_file_sorted(file_item);
-        }
+    repository_cache: &RepositoryCache,
+) -> bool {
+    if is_git_file(path, base_path) || !path.is_dir() {
+        return false;
     }
+
+    !git_workdir.is_some_and(|git_workdir| is_git_ignored(repository_cache, git_workdir, path))
 }
 
-fn remove_paths_from_index(
-    paths: Vec<PathBuf>,
-    sync_data: &Arc<RwLock<FileSync>>,
-    base_path: &Path,
-) {
-    if let Ok(mut sync_write) = sync_data.write() {
-        for path in paths {
-            if let Some(relative_path) = pathdiff::diff_paths(path, base_path) {
-                let relative_str = relative_path.to_string_lossy();
-                sync_write.remove_file_by_path(&relative_str);
-            }
+struct CachedRepository {
+    workdir: PathBuf,
+    head_modified: Option<SystemTime>,
+    repo: Repository,
+}
+
+fn head_modified_time(repo: &Repository) -> Option<SystemTime> {
+    std::fs::metadata(repo.path().join("HEAD"))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Repository shared by the watcher callbacks and the picker so it is opened once instead of
+/// for every event
+#[derive(Clone, Default)]
+struct RepositoryCache(Arc<Mutex<Option<CachedRepository>>>);
+
+impl RepositoryCache {
+    /// Runs `f` with the repository at `git_workdir`, opening it on first use. It is reopened
+    /// once `HEAD` changes, e.g. after a checkout, or after `invalidate`.
+    fn with_repository<R>(
+        &self,
+        git_workdir: &Path,
+        f: impl FnOnce(&Repository) -> R,
+    ) -> Option<R> {
+        // the repository is only read, so one left behind by a panicking holder is still fine
+        let mut cache = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let is_fresh = cache.as_ref().is_some_and(|cached| {
+            cached.workdir == git_workdir
+                && cached.head_modified == head_modified_time(&cached.repo)
+        });
+
+        if !is_fresh {
+            *cache = None;
+            let repo = Repository::open(git_workdir).ok()?;
+            *cache = Some(CachedRepository {
+                workdir: git_workdir.to_path_buf(),
+                head_modified: head_modified_time(&repo),
+                repo,
+            });
         }
+
+        cache.as_ref().map(|cached| f(&cached.repo))
     }
+
+    fn invalidate(&self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+fn is_git_ignored(repository_cache: &RepositoryCache, git_workdir: &Path, path: &Path) -> bool {
+    repository_cache
+        .with_repository(git_workdir, |repo| {
+            repo.is_path_ignored(path).unwrap_or(false)
+        })
+        .unwrap_or(false)
 }
 
-fn scan_filesystem(
+fn handle_create_events(
+    paths: &[PathBuf],
+    sync_data: &Arc<RwLock<FileSync>>,
     base_path: &Path,
     git_workdir: Option<&PathBuf>,
-) -> Result<(Vec<FileItem>, Option<GitStatusCache>), Error> {
+    repository_cache: &RepositoryCache,
+) {
+    // ignore checks wait for the repository and walking a new directory can take a while, so
+    // everything is prepared before taking the write lock
+    let mut new_files = Vec::new();
+    let mut directory_files = Vec::new();
+    for path in paths {
+        if git_workdir
+            .is_some_and(|git_workdir| is_git_ignored(repository_cache, git_workdir, path))
+        {
+            debug!("Ignoring file {} due to gitignore rules", path.display());
+            continue;
+        }
+
+        if path.is_dir() {
+            let files = collect_directory_files(
+                path,
+                base_path,
+                git_workdir.map(PathBuf::as_path),
+                repository_cache,
+            );
+            directory_files.push((path, files));
+        } else if let Some(mut file_item) = FileItem::new(path.clone(), base_path, None) {
+            file_item.update_frecency_scores();
+            new_files.push(file_item);
+        }
+    }
+
+    let mut sync_write = write_sync_data(sync_data);
+    for (path, files) in directory_files {
+        let inserted = sync_write.insert_files(files);
+        debug!(
+            "Indexed {} files under new directory {}",
+            inserted,
+            path.display()
+        );
+    }
+
+    for file_item in new_files {
+        // a rename whose remove event never arrived would leave the old path in the index
+        let renamed_from = sync_write
+            .find_file_by_inode(file_item.inode)
+            .filter(|old| old.path != file_item.path && !old.path.exists())
+            .map(|old| old.relative_path.clone());
+        if let Some(renamed_from) = renamed_from {
+            debug!(
+                "Detected rename of {} to {}",
+                renamed_from, file_item.relative_path
+            );
+            sync_write.batch_remove_paths(&[&renamed_from]);
+        }
+        // already indexed along with a directory created in the same batch of events
+        if sync_write.contains_path(&file_item.relative_path) {
+            continue;
+        }
+        sync_write.insert_file_sorted(file_item);
+    }
+}
+
+fn remove_paths_from_index(
+    paths: Vec<PathBuf>,
+    sync_data: &Arc<RwLock<FileSync>>,
+    base_path: &Path,
+) {
+    let keep_removed = SNAPSHOT_HISTORY_ENABLED.load(Ordering::Relaxed);
+    remove_paths_with_history(paths, sync_data, base_path, keep_removed);
+}
+
+/// `remove_paths_from_index` keeping the removed files in the snapshot history when
+/// `keep_removed`
+fn remove_paths_with_history(
+    paths: Vec<PathBuf>,
+    sync_data: &Arc<RwLock<FileSync>>,
+    base_path: &Path,
+    keep_removed: bool,
+) {
+    let relative_paths: Vec<String> = paths
+        .iter()
+        .filter_map(|path| relative_to(path, base_path))
+        .collect();
+    let relative_paths: Vec<&str> = relative_paths.iter().map(String::as_str).collect();
+
+    // check under the read lock first that the event removes anything at all, the write lock
+    // blocks searches
+    let removes_indexed_files = {
+        let sync_read = read_sync_data(sync_data);
+        relative_paths
+            .iter()
+            .any(|path| sync_read.contains_path(path) || sync_read.contains_prefix(path))
+    };
+    if !removes_indexed_files {
+        return;
+    }
+
+    let mut sync_write = write_sync_data(sync_data);
+    let generation = sync_write.scan_generation;
+    // only the removed files are copied, the history keeps nothing of the remaining ones
+    let removed_files: Vec<FileItem> = if keep_removed {
+        relative_paths
+            .iter()
+            .flat_map(|path| {
+                let file = sync_write
+                    .find_file_index(path)
+                    .ok()
+                    .map(|index| index..index + 1);
+                file.into_iter().chain([sync_write.prefix_range(path)])
+            })
+            .flat_map(|range| sync_write.files[range].iter().cloned())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    sync_write.batch_remove_paths(&relative_paths);
+
+    // paths that weren't files are deleted directories
+    for relative_path in relative_paths {
+        if sync_write.contains_prefix(relative_path) {
+            let removed = sync_write.remove_files_with_prefix(relative_path);
+            debug!(
+                "Removed {} files under deleted directory {}",
+                removed, relative_path
+            );
+        }
+    }
+
+    if sync_write.scan_generation != generation {
+        sync_write.push_snapshot(generation, removed_files);
+    }
+}
+
+/// Fails for base paths that can not be scanned, telling broken symlinks and files apart from
+/// paths that do not exist at all
+fn validate_base_path(path: &Path) -> Result<(), Error> {
+    if !path.exists() {
+        if path.symlink_metadata().is_ok() {
+            error!("Base path is a broken symlink: {}", path.display());
+            return Err(Error::BrokenSymlink(path.to_path_buf()));
+        }
+        error!("Base path does not exist: {}", path.display());
+        return Err(Error::InvalidPath(path.to_string_lossy().into_owned()));
+    }
+
+    if !path.is_dir() {
+        error!("Base path is not a directory: {}", path.display());
+        return Err(Error::NotADirectory(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+const SLOW_DIRECTORIES_LIMIT: usize = 10;
+
+struct ScanResult {
+    files: Vec<FileItem>,
+    git_cache: Option<GitStatusCache>,
+    slow_directories: Vec<SlowDirectory>,
+    skipped_file_count: usize,
+    branch_changed_files: HashSet<PathBuf>,
+    stash_count: usize,
+    duration: Duration,
+}
+
+fn scan_filesystem(base_path: &Path, git_workdir: Option<&PathBuf>) -> Result<ScanResult, Error> {
     let scan_start = std::time::Instant::now();
     let git_workdir = git_workdir.map(|p| p.as_path());
     info!("SCAN: Starting parallel filesystem scan and git status");
@@ -569,6 +2392,16 @@ fn scan_filesystem(
     // traversal which could be pretty slow on large repos (in general 300-500ms)
     thread::scope(|s| {
         let git_handle = s.spawn(|| GitStatusCache::read_git_status(git_workdir));
+        let branch_handle = s.spawn(|| {
+            if BRANCH_CHANGE_BONUS.load(Ordering::Relaxed) == 0 {
+                return HashSet::new();
+            }
+            read_branch_changes(git_workdir)
+        });
+        let stash_handle = s.spawn(|| list_stashes(git_workdir).len());
+
+        // opened once per scan, the watcher's cache is not needed for that
+        let submodule_paths = excluded_submodule_paths(git_workdir, &RepositoryCache::default());
 
         let walker = WalkBuilder::new(base_path)
             .hidden(false)
@@ -577,44 +2410,50 @@ fn scan_filesystem(
             .git_global(true)
             .ignore(true)
             .follow_links(false)
+            .threads(SCANNER_THREADS.load(Ordering::Relaxed))
             .sort_by_file_name(std::cmp::Ord::cmp)
+            .filter_entry(move |entry| !submodule_paths.contains(entry.path()))
             .build_parallel();
 
         let walker_start = std::time::Instant::now();
         info!("SCAN: Starting file walker");
 
-        let files = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let directory_timings = Arc::new(Mutex::new(DirectoryTimings::new()));
+        // entries pruned by ignore files are never visited by the walker so they are not counted
+        let skipped_file_count = Arc::new(AtomicUsize::new(0));
         walker.run(|| {
-            let files = Arc::clone(&files);
+            let paths = Arc::clone(&paths);
+            let mut directory_timer = DirectoryTimer::new(Arc::clone(&directory_timings));
+            let skipped_file_count = Arc::clone(&skipped_file_count);
             let base_path = base_path.to_path_buf();
 
             Box::new(move |result| {
-                if let Ok(entry) = result {
-                    if let Some(file_type) = entry.file_type() {
-                        if file_type.is_file() {
-                            let path = entry.path();
-
-                            if is_git_file(path) {
-                                return WalkState::Continue;
-                            }
-
-                            let file_item = FileItem::new(
-                                path.to_path_buf(),
-                                &base_path,
-                                None, // Git status will be added after join
-                            );
-
-                            if let Ok(mut files_vec) = files.lock() {
-                                files_vec.push(file_item);
-                            }
-                        }
+                let Ok(entry) = result else {
+                    skipped_file_count.fetch_add(1, Ordering::Relaxed);
+                    return WalkState::Continue;
+                };
+
+                directory_timer.record(&entry, &base_path);
+                if let Some(path) = indexable_path_from_entry(&entry, &base_path) {
+                    if let Ok(mut paths_vec) = paths.lock() {
+                        paths_vec.push(path);
                     }
+                } else if entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file())
+                {
+                    skipped_file_count.fetch_add(1, Ordering::Relaxed);
                 }
                 WalkState::Continue
             })
         });
 
-        let mut files = Arc::try_unwrap(files).unwrap().into_inner().unwrap();
+        let paths = Arc::try_unwrap(paths).unwrap().into_inner().unwrap();
+        let mut directory_timings = Arc::try_unwrap(directory_timings)
+            .unwrap()
+            .into_inner()
+            .unwrap();
         let walker_time = walker_start.elapsed();
         info!("SCAN: File walking completed in {:?}", walker_time);
 
@@ -622,11 +2461,24 @@ fn scan_filesystem(
             .join()
             .map_err(|_| Error::InvalidPath("Git status thread panicked".to_string()))?;
 
-        if let Some(git_cache) = &git_cache {
-            files.par_iter_mut().for_each(|file| {
-                file.git_status = git_cache.lookup_status(&file.path);
+        // items are only built once the git status is known so each is constructed in one go,
+        // the time it takes still counts towards the directory of the file
+        let (files, construction_times): (Vec<FileItem>, Vec<Duration>) = paths
+            .into_par_iter()
+            .filter_map(|path| {
+                let construction_start = std::time::Instant::now();
+                let mut file = FileItem::new_with_git_status(path, base_path, git_cache.as_ref())?;
                 file.update_frecency_scores();
-            });
+                Some((file, construction_start.elapsed()))
+            })
+            .unzip();
+        for (file, elapsed) in files.iter().zip(construction_times) {
+            if let Some(directory) = top_level_directory(Path::new(&file.relative_path)) {
+                let timing = directory_timings
+                    .entry(directory)
+                    .or_insert((0, Duration::ZERO));
+                timing.1 += elapsed;
+            }
         }
 
         let total_time = scan_start.elapsed();
@@ -636,13 +2488,186 @@ fn scan_filesystem(
             files.len()
         );
 
-        Ok((files, git_cache))
+        let branch_changed_files = branch_handle
+            .join()
+            .map_err(|_| Error::InvalidPath("Git branch diff thread panicked".to_string()))?;
+        let stash_count = stash_handle
+            .join()
+            .map_err(|_| Error::InvalidPath("Git stash thread panicked".to_string()))?;
+
+        Ok(ScanResult {
+            files,
+            git_cache,
+            slow_directories: slowest_directories(directory_timings),
+            skipped_file_count: skipped_file_count.load(Ordering::Relaxed),
+            branch_changed_files,
+            stash_count,
+            duration: total_time,
+        })
     })
 }
 
+/// Absolute paths of the submodules the scan leaves out, none unless they are excluded
+fn excluded_submodule_paths(
+    git_workdir: Option<&Path>,
+    repository_cache: &RepositoryCache,
+) -> HashSet<PathBuf> {
+    match git_workdir {
+        Some(git_workdir) if !INCLUDE_SUBMODULES.load(Ordering::Relaxed) => repository_cache
+            .with_repository(git_workdir, |repo| {
+                list_submodule_paths(repo)
+                    .into_iter()
+                    .map(|path| git_workdir.join(path))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        _ => HashSet::new(),
+    }
+}
+
+/// Items for every file under the newly created directory `dir` that the scan would index. Git
+/// statuses are left for the status update that follows the events.
+fn collect_directory_files(
+    dir: &Path,
+    base_path: &Path,
+    git_workdir: Option<&Path>,
+    repository_cache: &RepositoryCache,
+) -> Vec<FileItem> {
+    let submodule_paths = excluded_submodule_paths(git_workdir, repository_cache);
+    // the walker never filters its root
+    if submodule_paths.contains(dir) {
+        return Vec::new();
+    }
+
+    WalkBuilder::new(dir)
+        .hidden(false)
+        .git_ignore(true)
+        .git_exclude(true)
+        .git_global(true)
+        .ignore(true)
+        .follow_links(false)
+        .filter_entry(move |entry| !submodule_paths.contains(entry.path()))
+        .build()
+        .filter_map(Result::ok)
+        .filter_map(|entry| indexable_path_from_entry(&entry, base_path))
+        .filter_map(|path| {
+            let mut file = FileItem::new(path, base_path, None)?;
+            file.update_frecency_scores();
+            Some(file)
+        })
+        .collect()
+}
+
+/// Path of a walked entry when it is a file that belongs in the index
+fn indexable_path_from_entry(entry: &DirEntry, base_path: &Path) -> Option<PathBuf> {
+    if !entry.file_type()?.is_file() {
+        return None;
+    }
+
+    let path = entry.path();
+    if is_git_file(path, base_path) || is_platform_artifact(path) {
+        return None;
+    }
+
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    if !is_extension_allowed(&extension) {
+        return None;
+    }
+
+    Some(path.to_path_buf())
+}
+
+/// Entry count and walking time per top-level subdirectory of the base path
+type DirectoryTimings = HashMap<String, (usize, Duration)>;
+
+/// Times one walker thread. The walker reads a directory and stats its children right after
+/// handing the directory to the callback, so the gap until the thread's next callback is
+/// counted towards the previous entry. The thread's timings are merged into the shared ones
+/// once when the walker drops its callback.
+struct DirectoryTimer {
+    timings: DirectoryTimings,
+    merged: Arc<Mutex<DirectoryTimings>>,
+    last_callback: std::time::Instant,
+    last_directory: Option<String>,
+}
+
+impl DirectoryTimer {
+    fn new(merged: Arc<Mutex<DirectoryTimings>>) -> Self {
+        Self {
+            timings: DirectoryTimings::new(),
+            merged,
+            last_callback: std::time::Instant::now(),
+            last_directory: None,
+        }
+    }
+
+    fn record(&mut self, entry: &DirEntry, base_path: &Path) {
+        let now = std::time::Instant::now();
+        if let Some(directory) = self.last_directory.take() {
+            let timing = self.timings.entry(directory).or_insert((0, Duration::ZERO));
+            timing.1 += now - self.last_callback;
+        }
+        self.last_callback = now;
+
+        self.last_directory = entry
+            .path()
+            .strip_prefix(base_path)
+            .ok()
+            .and_then(top_level_directory);
+        if let Some(directory) = &self.last_directory {
+            self.timings
+                .entry(directory.clone())
+                .or_insert((0, Duration::ZERO))
+                .0 += 1;
+        }
+    }
+}
+
+impl Drop for DirectoryTimer {
+    fn drop(&mut self) {
+        let mut merged = self.merged.lock().unwrap_or_else(PoisonError::into_inner);
+        for (directory, (entry_count, elapsed)) in self.timings.drain() {
+            let timing = merged.entry(directory).or_insert((0, Duration::ZERO));
+            timing.0 += entry_count;
+            timing.1 += elapsed;
+        }
+    }
+}
+
+/// The top-level subdirectory `relative_path` is in, none for entries directly in the base path
+/// and for the top-level subdirectories themselves
+fn top_level_directory(relative_path: &Path) -> Option<String> {
+    let mut components = relative_path.components();
+    let Some(Component::Normal(top_level)) = components.next() else {
+        return None;
+    };
+    components.next()?;
+    Some(top_level.to_string_lossy().into_owned())
+}
+
+fn slowest_directories(timings: DirectoryTimings) -> Vec<SlowDirectory> {
+    let mut directories: Vec<SlowDirectory> = timings
+        .into_iter()
+        .map(|(directory, (entry_count, elapsed))| SlowDirectory {
+            directory,
+            entry_count,
+            elapsed_ms: elapsed.as_millis() as u64,
+        })
+        .collect();
+
+    directories.sort_unstable_by(|a, b| {
+        b.elapsed_ms
+            .cmp(&a.elapsed_ms)
+            .then_with(|| b.entry_count.cmp(&a.entry_count))
+    });
+    directories.truncate(SLOW_DIRECTORIES_LIMIT);
+    directories
+}
+
 fn update_git_status_for_paths(
     sync_data: &Arc<RwLock<FileSync>>,
     git_workdir: &Option<PathBuf>,
+    repository_cache: &RepositoryCache,
     base_path: &Path,
     affected_paths: &[PathBuf],
 ) {
@@ -650,18 +2675,39 @@ fn update_git_status_for_paths(
         return;
     };
 
-    let Ok(repo) = Repository::open(git_workdir) else {
+    // the statuses are read first so the repository is not held while waiting for the write lock
+    let Some(statuses) = repository_cache
+        .with_repository(git_workdir, |repo| {
+            read_path_statuses(repo, base_path, affected_paths)
+        })
+        .flatten()
+    else {
         return;
     };
 
+    let mut sync_write = write_sync_data(sync_data);
+    for (file_path, status) in statuses {
+        if let Ok(index) = sync_write.find_file_index(&file_path) {
+            sync_write.files[index].git_status = Some(status);
+            sync_write.files[index].update_frecency_scores();
+        }
+    }
+}
+
+fn read_path_statuses(
+    repo: &Repository,
+    base_path: &Path,
+    affected_paths: &[PathBuf],
+) -> Option<Vec<(String, Status)>> {
     let mut status_options = StatusOptions::new();
     status_options.include_untracked(true);
+    // files in new directories are otherwise reported as the directory itself
+    status_options.recurse_untracked_dirs(true);
     status_options.include_ignored(false);
 
     for path in affected_paths {
-        if let Some(relative_path) = pathdiff::diff_paths(path, base_path) {
-            let path_str = relative_path.to_string_lossy();
-            status_options.pathspec(&*path_str);
+        if let Some(relative_path) = relative_to(path, base_path) {
+            status_options.pathspec(relative_path);
         }
     }
 
@@ -670,30 +2716,1986 @@ fn update_git_status_for_paths(
             "Failed to get git statuses for affected paths: {:?}",
             affected_paths
         );
-        return;
+        return None;
     };
 
-    if let Ok(mut sync_write) = sync_data.write() {
-        for status_entry in statuses.iter() {
-            let Some(file_path) = status_entry.path() else {
-                continue;
-            };
+    Some(
+        statuses
+            .iter()
+            .filter_map(|status_entry| {
+                let file_path = status_entry.path()?;
+                Some((file_path.to_string(), status_entry.status()))
+            })
+            .collect(),
+    )
+}
 
-            if let Ok(index) = sync_write.find_file_index(file_path) {
-                sync_write.files[index].git_status = Some(status_entry.status());
-                sync_write.files[index].update_frecency_scores();
-            }
-        }
+fn is_extension_allowed(extension: &str) -> bool {
+    let included = INCLUDED_EXTENSIONS
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+    let excluded = EXCLUDED_EXTENSIONS
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+    extension_passes_filters(extension, &included, &excluded)
+}
+
+/// A non-empty allowlist takes priority over the excluded extensions, both are lowercase
+fn extension_passes_filters(
+    extension: &str,
+    included: &HashSet<String>,
+    excluded: &HashSet<String>,
+) -> bool {
+    let extension = extension.to_lowercase();
+    if !included.is_empty() {
+        return included.contains(&extension);
     }
+    !excluded.contains(&extension)
 }
 
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Whether the path is inside a `.git` directory, only components below `base_path` are
+/// considered so a base path that itself lives inside `.git` does not reject every file
 #[inline]
-fn is_git_file(path: &Path) -> bool {
-    path.to_str().is_some_and(|path| path.contains("/.git/"))
+fn is_git_file(path: &Path, base_path: &Path) -> bool {
+    path.strip_prefix(base_path)
+        .unwrap_or(path)
+        .components()
+        .any(|component| component == Component::Normal(OsStr::new(".git")))
+}
+
+fn is_platform_artifact(path: &Path) -> bool {
+    if !EXCLUDE_PLATFORM_ARTIFACTS.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    is_macos_resource_fork(&name) || is_ntfs_ads(&name)
 }
 
 impl Drop for FilePicker {
     fn drop(&mut self) {
-        self.shutdown_signal.store(true, Ordering::Relaxed);
+        self.stop_background_monitor();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{files_changed_since_base, find_merge_base, is_sparse_checkout};
+    use crate::log_buffer::{recent_logs, LogBufferLayer};
+    use crate::score::tests::test_scoring_context;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Empty directory under the system temp directory, removed again when dropped
+    pub(super) struct TestDir {
+        pub(super) path: PathBuf,
+    }
+
+    impl TestDir {
+        /// `name` keeps the tests apart, the process id concurrent test runs
+        pub(super) fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("fff_{}_{}", name, std::process::id()));
+            std::fs::remove_dir_all(&path).ok();
+            std::fs::create_dir_all(&path).unwrap();
+            Self { path }
+        }
+
+        /// Picker over the directory whose initial scan has completed
+        pub(super) fn scanned_picker(&self) -> FilePicker {
+            let picker =
+                FilePicker::with_file_cache(self.path.to_string_lossy().into_owned(), None)
+                    .unwrap();
+            let (scan_signal, scan_complete) = picker.scan_waiter();
+            assert!(wait_for_scan(
+                &scan_signal,
+                &scan_complete,
+                Duration::from_secs(60)
+            ));
+            picker
+        }
+    }
+
+    impl std::ops::Deref for TestDir {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.path
+        }
+    }
+
+    impl AsRef<Path> for TestDir {
+        fn as_ref(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.path).ok();
+        }
+    }
+
+    /// Writes `files` and commits them on top of HEAD, returns the new commit
+    fn commit_files(repo: &Repository, files: &[(&str, &str)], message: &str) -> git2::Oid {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            let full_path = workdir.join(path);
+            std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            std::fs::write(full_path, content).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("fff", "fff@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_wait_right_after_construction_waits_for_the_initial_scan() {
+        let dir = TestDir::new("wait_after_new");
+        std::fs::write(dir.join("main.rs"), "").unwrap();
+
+        let picker = FilePicker::with_file_cache(dir.to_string_lossy().into_owned(), None).unwrap();
+        let (scan_signal, scan_complete) = picker.scan_waiter();
+        assert!(wait_for_scan(
+            &scan_signal,
+            &scan_complete,
+            Duration::from_secs(60)
+        ));
+        assert!(read_sync_data(&picker.sync_data).contains_path("main.rs"));
+    }
+
+    #[test]
+    fn test_reset_is_not_blocked_by_wait_for_scan() {
+        let dir = TestDir::new("wait_for_scan");
+        let picker = dir.scanned_picker();
+        let (scan_signal, scan_complete) = picker.scan_waiter();
+        let picker_lock = RwLock::new(Some(picker));
+
+        // pretend a slow scan is running so the waiter stays blocked
+        scan_signal.store(true, Ordering::Relaxed);
+        thread::scope(|s| {
+            let waiter =
+                s.spawn(|| wait_for_picker_scan(&picker_lock, Duration::from_secs(10)).unwrap());
+            thread::sleep(Duration::from_millis(100));
+            assert!(!waiter.is_finished());
+
+            let reset_start = std::time::Instant::now();
+            *picker_lock.write().unwrap() = None;
+            assert!(reset_start.elapsed() < Duration::from_millis(500));
+
+            let wait_start = std::time::Instant::now();
+            finish_scan(&scan_signal, &scan_complete);
+            assert!(waiter.join().unwrap());
+            assert!(wait_start.elapsed() < Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn test_ignore_file_outside_git_repository() {
+        let dir = TestDir::new("ignore_file");
+        std::fs::write(dir.join(".ignore"), "*.log\n").unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("debug.log"), "log line\n").unwrap();
+
+        // `WalkBuilder::ignore` reads `.ignore` files whether or not a git repository exists
+        let picker = dir.scanned_picker();
+
+        let files = picker.get_cached_files();
+        assert!(files.iter().any(|f| f.relative_path == "main.rs"));
+        assert!(!files.iter().any(|f| f.extension == "log"));
+    }
+
+    #[test]
+    fn test_single_scanner_thread_finds_the_same_files() {
+        let dir = TestDir::new("scanner_threads");
+        for module in 0..8 {
+            for file in 0..16 {
+                let path = dir.join(format!("src/module_{}/file_{}.rs", module, file));
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, "content\n").unwrap();
+            }
+        }
+
+        let scanned_paths = || {
+            let mut paths: Vec<String> = scan_filesystem(&dir, None)
+                .unwrap()
+                .files
+                .into_iter()
+                .map(|file| file.relative_path)
+                .collect();
+            paths.sort_unstable();
+            paths
+        };
+        let parallel = scanned_paths();
+        SCANNER_THREADS.store(1, Ordering::Relaxed);
+        let single_threaded = scanned_paths();
+        SCANNER_THREADS.store(0, Ordering::Relaxed);
+
+        assert_eq!(parallel.len(), 128);
+        assert_eq!(single_threaded, parallel);
+    }
+
+    #[test]
+    fn test_file_item_outside_of_base_path() {
+        let base = Path::new("/home/user/project");
+        let file = FileItem::new(base.join("src/main.rs"), base, None).unwrap();
+        assert_eq!(file.relative_path, "src/main.rs");
+        assert!(FileItem::new(PathBuf::from("/home/user/other/main.rs"), base, None).is_none());
+    }
+
+    #[test]
+    fn test_scan_times_directories() {
+        let dir = TestDir::new("scan_directory_timings");
+        std::fs::create_dir_all(dir.join("src/nested")).unwrap();
+        std::fs::write(dir.join("main.rs"), "").unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        std::fs::write(dir.join("src/nested/mod.rs"), "").unwrap();
+
+        let slow_directories = scan_filesystem(&dir, None).unwrap().slow_directories;
+        assert_eq!(slow_directories.len(), 1);
+        assert_eq!(slow_directories[0].directory, "src");
+        // src/lib.rs, src/nested and src/nested/mod.rs
+        assert_eq!(slow_directories[0].entry_count, 3);
+
+        // the time until the next callback belongs to the entry handed over before it
+        let entry = |path: &str| {
+            WalkBuilder::new(dir.join(path))
+                .build()
+                .next()
+                .unwrap()
+                .unwrap()
+        };
+        let merged = Arc::new(Mutex::new(DirectoryTimings::new()));
+        let mut timer = DirectoryTimer::new(Arc::clone(&merged));
+        timer.record(&entry("src/nested"), &dir);
+        thread::sleep(Duration::from_millis(20));
+        timer.record(&entry("main.rs"), &dir);
+        // main.rs is not in a subdirectory and the last gap is never counted
+        thread::sleep(Duration::from_millis(200));
+        drop(timer);
+        let merged = merged.lock().unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged["src"].0, 1);
+        assert!(merged["src"].1 >= Duration::from_millis(20));
+        assert!(merged["src"].1 < Duration::from_millis(200));
+
+        assert_eq!(
+            top_level_directory(Path::new("src/nested/mod.rs")),
+            Some("src".to_string())
+        );
+        assert_eq!(top_level_directory(Path::new("src")), None);
+        assert_eq!(top_level_directory(Path::new("main.rs")), None);
+    }
+
+    #[test]
+    fn test_scan_sets_git_status_of_new_files() {
+        let dir = TestDir::new("scan_git_status");
+        Repository::init(&dir).unwrap();
+        std::fs::write(dir.join("untracked.rs"), "fn main() {}\n").unwrap();
+
+        let files = scan_filesystem(&dir, Some(&dir)).unwrap().files;
+        let untracked = files
+            .iter()
+            .find(|file| file.relative_path == "untracked.rs")
+            .unwrap();
+        assert_eq!(untracked.git_status, Some(Status::WT_NEW));
+
+        let files = scan_filesystem(&dir, None).unwrap().files;
+        assert!(files.iter().all(|file| file.git_status.is_none()));
+    }
+
+    #[test]
+    fn test_scan_skips_submodules() {
+        let dir = TestDir::new("scan_submodules");
+        let repo = Repository::init(&dir).unwrap();
+        std::fs::write(
+            dir.join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = https://example.com/lib.git\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("vendor/lib")).unwrap();
+        std::fs::write(dir.join("vendor/lib/lib.rs"), "").unwrap();
+        std::fs::write(dir.join("vendor/mod.rs"), "").unwrap();
+
+        assert_eq!(list_submodule_paths(&repo), [PathBuf::from("vendor/lib")]);
+        let mut paths: Vec<String> = scan_filesystem(&dir, Some(&dir))
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|file| file.relative_path.replace('\\', "/"))
+            .collect();
+        paths.sort();
+        assert_eq!(paths, [".gitmodules", "vendor/mod.rs"]);
+    }
+
+    #[test]
+    fn test_scan_counts_stashes() {
+        let dir = TestDir::new("scan_stashes");
+        let mut repo = Repository::init(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+        let signature = git2::Signature::now("fff", "fff@example.com").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("main.rs")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        drop(tree);
+
+        std::fs::write(dir.join("main.rs"), "fn main() { todo!() }\n").unwrap();
+        repo.stash_save(&signature, "work in progress", None)
+            .unwrap();
+
+        assert_eq!(scan_filesystem(&dir, Some(&dir)).unwrap().stash_count, 1);
+        let stashes = list_stashes(Some(&dir));
+        assert_eq!(stashes.len(), 1);
+        assert_eq!(stashes[0].index, 0);
+        assert!(stashes[0].message.contains("work in progress"));
+        assert!(list_stashes(None).is_empty());
+    }
+
+    #[test]
+    fn test_files_changed_since_merge_base() {
+        let dir = TestDir::new("branch_changes");
+        let repo = Repository::init(&dir).unwrap();
+        let base = commit_files(&repo, &[("main.rs", "fn main() {}\n")], "init");
+        repo.branch("upstream", &repo.find_commit(base).unwrap(), false)
+            .unwrap();
+        commit_files(&repo, &[("src/feature.rs", "")], "feature");
+        std::fs::write(dir.join("main.rs"), "fn main() { todo!() }\n").unwrap();
+
+        assert_eq!(find_merge_base(&repo, "upstream"), Some(base));
+        assert_eq!(find_merge_base(&repo, "missing"), None);
+        assert_eq!(
+            files_changed_since_base(&repo, base),
+            HashSet::from([dir.join("src/feature.rs"), dir.join("main.rs")])
+        );
+    }
+
+    #[test]
+    fn test_diff_against_head() {
+        let dir = TestDir::new("diff_against_head");
+        let repo = Repository::init(&dir).unwrap();
+        commit_files(&repo, &[("main.rs", "first\nsecond\nthird\n")], "init");
+        std::fs::write(dir.join("main.rs"), "first\nchanged\nthird\n").unwrap();
+        std::fs::write(dir.join("untracked.rs"), "").unwrap();
+
+        let diff = diff_against_head(&dir, &dir.join("main.rs"), 3).unwrap();
+        assert!(diff.contains("--- a/main.rs\n+++ b/main.rs\n"));
+        assert!(diff.contains("\n first\n-second\n+changed\n third\n"));
+        let diff = diff_against_head(&dir, &dir.join("main.rs"), 0).unwrap();
+        assert!(diff.ends_with("@@ -2 +2 @@ first\n-second\n+changed\n"));
+
+        assert_eq!(diff_against_head(&dir, &dir.join("untracked.rs"), 3), None);
+        assert_eq!(diff_against_head(&dir, &dir.join("missing.rs"), 3), None);
+    }
+
+    #[test]
+    fn test_staging_refreshes_git_status() {
+        let dir = TestDir::new("git_index_watch");
+        let repo = Repository::init(&dir).unwrap();
+        commit_files(&repo, &[("main.rs", "fn main() {}\n")], "init");
+        std::fs::write(dir.join("new.rs"), "").unwrap();
+
+        let sync_data = Arc::new(RwLock::new(FileSync::new()));
+        let scan = scan_filesystem(&dir, Some(&dir)).unwrap();
+        write_sync_data(&sync_data).update_files(scan.files, scan.git_cache);
+        let status_of = |path: &str| {
+            let sync = read_sync_data(&sync_data);
+            sync.files
+                .iter()
+                .find(|file| file.relative_path == path)
+                .and_then(|file| file.git_status)
+        };
+        assert_eq!(status_of("new.rs"), Some(Status::WT_NEW));
+
+        let _debouncer = watch_git_index(
+            &dir,
+            &sync_data,
+            &Arc::new(AtomicBool::new(false)),
+            &RepositoryCache::default(),
+            &Arc::new(AtomicBool::new(false)),
+            &Arc::new(AtomicBool::new(false)),
+        )
+        .unwrap();
+
+        // staging only rewrites .git/index, the working tree doesn't change
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("new.rs")).unwrap();
+        index.write().unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while status_of("new.rs") != Some(Status::INDEX_NEW) {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "git status was not refreshed after staging"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_sparse_checkout_skips_statuses_of_missing_files() {
+        let dir = TestDir::new("sparse_checkout");
+        let repo = Repository::init(&dir).unwrap();
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("docs/guide.md"), "# Guide\n").unwrap();
+
+        let signature = git2::Signature::now("fff", "fff@example.com").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("main.rs")).unwrap();
+        index.add_path(Path::new("docs/guide.md")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+
+        // a file left out of the sparse patterns, and a real change
+        std::fs::remove_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() { todo!() }\n").unwrap();
+
+        assert!(!is_sparse_checkout(&repo));
+        let cache = GitStatusCache::read_repository_status(&repo, &dir).unwrap();
+        assert_eq!(cache.changed_file_count(), 2);
+
+        repo.config()
+            .unwrap()
+            .set_bool("core.sparseCheckout", true)
+            .unwrap();
+        assert!(is_sparse_checkout(&repo));
+        let cache = GitStatusCache::read_repository_status(&repo, &dir).unwrap();
+        assert_eq!(cache.changed_file_count(), 1);
+        assert!(cache.lookup_status(&dir.join("main.rs")).is_some());
+        assert!(cache.lookup_status(&dir.join("docs/guide.md")).is_none());
+    }
+
+    #[test]
+    fn test_supervise_watcher_restarts_panicked_watcher() {
+        let shutdown = AtomicBool::new(false);
+        let restart_count = AtomicU32::new(0);
+        let mut runs = 0;
+        supervise_watcher(&shutdown, &restart_count, Duration::from_millis(10), || {
+            runs += 1;
+            let crashes = runs < 3;
+            thread::spawn(move || assert!(!crashes, "watcher crashed"))
+        });
+        assert_eq!(runs, 3);
+        assert_eq!(restart_count.load(Ordering::Relaxed), 2);
+
+        // no restarts once shutting down
+        shutdown.store(true, Ordering::Relaxed);
+        let mut runs = 0;
+        supervise_watcher(&shutdown, &restart_count, Duration::from_millis(10), || {
+            runs += 1;
+            thread::spawn(|| panic!("watcher crashed"))
+        });
+        assert_eq!(runs, 1);
+        assert_eq!(restart_count.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_supervise_watcher_gives_up_on_a_watcher_that_keeps_panicking() {
+        let shutdown = AtomicBool::new(false);
+        let restart_count = AtomicU32::new(0);
+        let mut runs = 0;
+        let start = std::time::Instant::now();
+        supervise_watcher(&shutdown, &restart_count, Duration::from_millis(5), || {
+            runs += 1;
+            thread::spawn(|| panic!("watcher crashed"))
+        });
+        assert_eq!(runs, WATCHER_RESTART_LIMIT + 1);
+        assert_eq!(restart_count.load(Ordering::Relaxed), WATCHER_RESTART_LIMIT);
+        // 5 + 10 + 20 + 40 + 80
+        assert!(start.elapsed() >= Duration::from_millis(155));
+    }
+
+    #[test]
+    fn test_panicking_watcher_callback_is_reported() {
+        let panicked = AtomicBool::new(false);
+        run_watcher_callback(&panicked, || {});
+        assert!(!panicked.load(Ordering::Relaxed));
+
+        run_watcher_callback(&panicked, || panic!("callback crashed"));
+        assert!(panicked.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_changes_while_paused_are_rescanned_on_resume() {
+        let dir = TestDir::new("paused_changes");
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        let picker = dir.scanned_picker();
+        let wait_until_indexed = |path: &str, timeout: Duration| {
+            let deadline = std::time::Instant::now() + timeout;
+            while !read_sync_data(&picker.sync_data).contains_path(path) {
+                if std::time::Instant::now() >= deadline {
+                    return false;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            true
+        };
+        // the watcher only starts watching after the initial scan, files created before are missed
+        let watching = (0..20).any(|attempt| {
+            let probe = format!("probe_{}.rs", attempt);
+            std::fs::write(dir.join(&probe), "").unwrap();
+            wait_until_indexed(&probe, WATCHER_DEBOUNCE * 2)
+        });
+        assert!(watching);
+
+        picker.paused.store(true, Ordering::Relaxed);
+        std::fs::write(dir.join("new.rs"), "").unwrap();
+        thread::sleep(WATCHER_DEBOUNCE * 3);
+        assert!(!read_sync_data(&picker.sync_data).contains_path("new.rs"));
+
+        picker.paused.store(false, Ordering::Relaxed);
+        assert!(wait_until_indexed("new.rs", Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_set_base_path() {
+        let old_dir = TestDir::new("set_base_path_old");
+        let new_dir = TestDir::new("set_base_path_new");
+        std::fs::write(old_dir.join("old.rs"), "").unwrap();
+        std::fs::write(new_dir.join("new.rs"), "").unwrap();
+        let relative_paths = |picker: &FilePicker| -> Vec<String> {
+            picker
+                .get_cached_files()
+                .into_iter()
+                .map(|file| file.relative_path)
+                .collect()
+        };
+
+        let mut picker = old_dir.scanned_picker();
+        assert_eq!(relative_paths(&picker), ["old.rs"]);
+        picker.record_search_selection("old".to_string(), "old.rs".to_string());
+        let old_sync_data = Arc::clone(&picker.sync_data);
+        let old_watcher_shutdown = Arc::clone(&picker.watcher_shutdown);
+
+        picker
+            .set_base_path(new_dir.to_string_lossy().into_owned())
+            .unwrap();
+        // only the watcher starts over, its data is not shared with the previous one
+        assert!(old_watcher_shutdown.load(Ordering::Relaxed));
+        assert!(!picker.shutdown_signal.load(Ordering::Relaxed));
+        assert!(!Arc::ptr_eq(&old_sync_data, &picker.sync_data));
+        assert_eq!(picker.search_history(10).len(), 1);
+        let (scan_signal, scan_complete) = picker.scan_waiter();
+        assert!(wait_for_scan(
+            &scan_signal,
+            &scan_complete,
+            Duration::from_secs(60)
+        ));
+        assert_eq!(picker.base_path(), new_dir.as_path());
+        assert_eq!(relative_paths(&picker), ["new.rs"]);
+
+        // a failed switch keeps the current picker
+        assert!(picker
+            .set_base_path(new_dir.join("missing").to_string_lossy().into_owned())
+            .is_err());
+        assert_eq!(picker.base_path(), new_dir.as_path());
+        assert_eq!(relative_paths(&picker), ["new.rs"]);
+    }
+
+    #[test]
+    fn test_preferred_extensions_rank_higher() {
+        let dir = TestDir::new("preferred_extensions");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        for path in ["src/a.rs", "src/b.rs", "src/c.rs", "user.rs", "user.md"] {
+            std::fs::write(dir.join(path), "").unwrap();
+        }
+        let picker = dir.scanned_picker();
+        let top_result = |picker: &FilePicker| {
+            let result = picker.fuzzy_search("user", 10, 1, None, None);
+            let totals: Vec<(String, i32)> = result
+                .items
+                .into_iter()
+                .zip(result.scores)
+                .filter(|(file, _)| file.file_name.starts_with("user"))
+                .map(|(file, score)| (file.relative_path, score.total))
+                .collect();
+            assert!(totals[0].1 > totals[1].1);
+            totals[0].0.clone()
+        };
+
+        assert_eq!(picker.get_detected_language().as_deref(), Some("rust"));
+        assert_eq!(top_result(&picker), "user.rs");
+
+        picker
+            .set_preferred_extensions(HashSet::from(["md".to_string()]))
+            .unwrap();
+        assert_eq!(top_result(&picker), "user.md");
+        // later scans keep the configured extensions
+        picker.trigger_rescan().unwrap();
+        let (scan_signal, scan_complete) = picker.scan_waiter();
+        assert!(wait_for_scan(
+            &scan_signal,
+            &scan_complete,
+            Duration::from_secs(60)
+        ));
+        assert_eq!(top_result(&picker), "user.md");
+    }
+
+    #[test]
+    fn test_search_history_keeps_most_recent_selections() {
+        let dir = TestDir::new("search_history");
+        let picker = FilePicker::with_file_cache(dir.to_string_lossy().into_owned(), None).unwrap();
+
+        for i in 0..SEARCH_HISTORY_CAPACITY + 5 {
+            picker.record_search_selection(format!("query {}", i), format!("file_{}.rs", i));
+        }
+        let history = picker.search_history(usize::MAX);
+        assert_eq!(history.len(), SEARCH_HISTORY_CAPACITY);
+        assert_eq!(
+            history[0].query,
+            format!("query {}", SEARCH_HISTORY_CAPACITY + 4)
+        );
+        assert_eq!(history.last().unwrap().selected_path, "file_5.rs");
+        assert_eq!(picker.search_history(2).len(), 2);
+
+        picker.clear_search_history();
+        assert!(picker.search_history(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let dir = TestDir::new("search_modes");
+        std::fs::create_dir_all(dir.join("src/parser")).unwrap();
+        for path in ["src/parser/lexer.rs", "src/main.rs", "docs/Parser.md"] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "content\n").unwrap();
+        }
+
+        let picker = dir.scanned_picker();
+
+        let paths = |query: &str, mode: SearchMode| -> Vec<String> {
+            let mut paths: Vec<String> = picker
+                .search(query, mode, 10, 1, None, None)
+                .items
+                .into_iter()
+                .map(|f| f.relative_path.replace('\\', "/"))
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(paths("parser", SearchMode::Exact), ["src/parser/lexer.rs"]);
+        assert_eq!(
+            paths(r"\.rs$", SearchMode::Regex),
+            ["src/main.rs", "src/parser/lexer.rs"]
+        );
+        assert!(paths("(", SearchMode::Regex).is_empty());
+        assert_eq!(
+            paths("parser", SearchMode::Anchored),
+            ["docs/Parser.md", "src/parser/lexer.rs"]
+        );
+        let totals = |result: SearchResult| -> Vec<(String, i32)> {
+            result
+                .items
+                .into_iter()
+                .map(|f| f.relative_path)
+                .zip(result.scores.into_iter().map(|score| score.total))
+                .collect()
+        };
+        assert_eq!(
+            totals(picker.anchored_search("parser", 10, 1)),
+            totals(picker.search("parser", SearchMode::Anchored, 10, 1, None, None))
+        );
+        assert_eq!(paths("@ext:md", SearchMode::Fuzzy), ["docs/Parser.md"]);
+        assert_eq!(
+            paths("@dir:src/parser", SearchMode::Fuzzy),
+            ["src/parser/lexer.rs"]
+        );
+
+        let result = picker.search("lex @dir:src/parser", SearchMode::Fuzzy, 10, 1, None, None);
+        let directory = result.directory.as_deref().unwrap();
+        assert_eq!(directory, "src/parser");
+        assert_eq!(result.items[0].relative_to_directory(directory), "lexer.rs");
+        assert!(picker
+            .search("lexer", SearchMode::Fuzzy, 10, 1, None, None)
+            .directory
+            .is_none());
+    }
+
+    #[test]
+    fn test_file_cache_roundtrip() {
+        let dir = TestDir::new("file_cache");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let mut sync = FileSync::new();
+        sync.update_files(
+            vec![FileItem::new(dir.join("src/main.rs"), &dir, None).unwrap()],
+            None,
+        );
+
+        let cache_path = dir.join("cache/files.json");
+        sync.save_to_disk(&cache_path, &dir).unwrap();
+
+        let loaded = FileSync::load_from_disk(&cache_path, &dir).unwrap();
+        assert_eq!(loaded.files.len(), 1);
+        assert_eq!(loaded.files[0].relative_path, sync.files[0].relative_path);
+        assert_eq!(loaded.files[0].path, dir.join("src/main.rs"));
+        assert_eq!(loaded.files[0].size, 12);
+
+        assert!(matches!(
+            FileSync::load_from_disk(&cache_path, Path::new("/some/other/project")),
+            Err(Error::FileCacheBasePathMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_cache_does_not_replace_scanned_files() {
+        let dir = TestDir::new("file_cache_after_scan");
+        std::fs::write(dir.join("a.rs"), "").unwrap();
+        let cache_dir = TestDir::new("file_cache_after_scan_cache");
+        let cache_path = cache_dir.join("files.json");
+        dir.scanned_picker().save_file_cache(&cache_path).unwrap();
+
+        std::fs::write(dir.join("b.rs"), "").unwrap();
+        let picker = dir.scanned_picker();
+        assert_eq!(picker.load_file_cache(&cache_path).unwrap(), 0);
+        let mut paths: Vec<String> = picker
+            .get_cached_files()
+            .into_iter()
+            .map(|file| file.relative_path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn test_events_outside_of_base_path_are_ignored() {
+        let dir = TestDir::new("outside_events");
+        let base = dir.join("project");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("outside.rs"), "").unwrap();
+        let sync_data = Arc::new(RwLock::new(FileSync::new()));
+        write_sync_data(&sync_data).update_files(scan_filesystem(&base, None).unwrap().files, None);
+        let generation = read_sync_data(&sync_data).scan_generation;
+
+        let event = |kind: EventKind, path: PathBuf| {
+            DebouncedEvent::new(
+                notify::Event::new(kind).add_path(path),
+                std::time::Instant::now(),
+            )
+        };
+        let log_guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(LogBufferLayer));
+        handle_debounced_events(
+            vec![
+                event(
+                    EventKind::Create(notify::event::CreateKind::File),
+                    dir.join("outside.rs"),
+                ),
+                event(
+                    EventKind::Remove(notify::event::RemoveKind::Any),
+                    dir.clone(),
+                ),
+            ],
+            &sync_data,
+            &base,
+            &None,
+            &RepositoryCache::default(),
+        );
+        drop(log_guard);
+
+        let sync = read_sync_data(&sync_data);
+        assert_eq!(sync.files.len(), 1);
+        assert_eq!(sync.files[0].relative_path, "main.rs");
+        assert_eq!(sync.scan_generation, generation);
+        // expected events, relativizing them used to warn about each one
+        let outside_path = dir.join("outside.rs").display().to_string();
+        assert!(!recent_logs(usize::MAX, Some(tracing::Level::WARN))
+            .iter()
+            .any(|line| line.contains(&outside_path)));
+    }
+
+    #[test]
+    fn test_coalesce_save_renames() {
+        let dir = TestDir::new("save_renames");
+        let original = dir.join("main.rs");
+        let temporary = dir.join("main.rs.tmp");
+        std::fs::write(&original, "fn main() {}").unwrap();
+
+        let event = |kind: EventKind, path: &Path| {
+            DebouncedEvent::new(
+                notify::Event::new(kind).add_path(path.to_path_buf()),
+                std::time::Instant::now(),
+            )
+        };
+        let events = vec![
+            event(
+                EventKind::Create(notify::event::CreateKind::File),
+                &temporary,
+            ),
+            event(
+                EventKind::Remove(notify::event::RemoveKind::File),
+                &original,
+            ),
+            event(
+                EventKind::Create(notify::event::CreateKind::File),
+                &original,
+            ),
+            event(
+                EventKind::Remove(notify::event::RemoveKind::File),
+                &temporary,
+            ),
+        ];
+
+        let coalesced =
+            coalesce_save_renames(events, &mut VecDeque::new(), |path| path == original);
+        assert_eq!(coalesced.len(), 1);
+        assert_eq!(coalesced[0].paths, vec![original.clone()]);
+        assert!(coalesced[0].kind.is_modify());
+    }
+
+    #[test]
+    fn test_write_sync_data_recovers_poisoned_lock() {
+        let sync_data = RwLock::new(FileSync::new());
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = sync_data.write().unwrap();
+            panic!("poison the lock");
+        });
+        assert!(sync_data.is_poisoned());
+
+        write_sync_data(&sync_data).scan_generation = 1;
+        assert!(!sync_data.is_poisoned());
+        assert_eq!(sync_data.read().unwrap().scan_generation, 1);
+    }
+
+    #[test]
+    fn test_picker_reads_survive_poisoned_lock() {
+        let dir = TestDir::new("poisoned_reads");
+        std::fs::write(dir.join("main.rs"), "").unwrap();
+        let picker = dir.scanned_picker();
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = picker.sync_data.write().unwrap();
+            panic!("poison the lock");
+        });
+        assert!(picker.sync_data.is_poisoned());
+
+        assert_eq!(
+            picker.fuzzy_search("main", 10, 1, None, None).items.len(),
+            1
+        );
+        assert!(!picker.sync_data.is_poisoned());
+        assert_eq!(picker.anchored_search("main", 10, 1).items.len(), 1);
+        assert_eq!(picker.get_cached_files().len(), 1);
+        assert_eq!(picker.get_debug_info().file_count, 1);
+        assert_eq!(picker.get_skipped_file_count(), 0);
+    }
+
+    #[test]
+    fn test_remove_files_with_prefix() {
+        let base = Path::new("/project");
+        let mut sync = FileSync::new();
+        sync.update_files(
+            [
+                "src/a.rs",
+                "src/nested/b.rs",
+                "src-old/c.rs",
+                "srcfile.rs",
+                "tests/d.rs",
+            ]
+            .iter()
+            .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+            .collect(),
+            None,
+        );
+
+        assert!(sync.contains_prefix("src"));
+        assert!(!sync.contains_prefix("docs"));
+        assert_eq!(sync.remove_files_with_prefix("src"), 2);
+
+        let remaining: Vec<PathBuf> = sync
+            .files
+            .iter()
+            .map(|f| PathBuf::from(&f.relative_path))
+            .collect();
+        assert_eq!(
+            remaining,
+            ["src-old/c.rs", "srcfile.rs", "tests/d.rs"].map(PathBuf::from)
+        );
+        assert_eq!(sync.remove_files_with_prefix("docs"), 0);
+    }
+
+    #[test]
+    fn test_repository_cache_reuses_repository() {
+        let dir = TestDir::new("cached_repo");
+        Repository::init(&dir).unwrap();
+
+        let cache = RepositoryCache::default();
+        let open = || cache.with_repository(&dir, |repo| repo as *const Repository as usize);
+        let first = open();
+        assert!(first.is_some());
+        assert_eq!(open(), first);
+
+        cache.invalidate();
+        assert!(cache.0.lock().unwrap().is_none());
+        assert!(open().is_some());
+        assert_eq!(cache.with_repository(&dir.join("missing"), |_| ()), None);
+    }
+
+    #[test]
+    fn test_update_files_reports_diff() {
+        let base = Path::new("/project");
+        let items = |paths: &[&str]| -> Vec<FileItem> {
+            paths
+                .iter()
+                .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+                .collect()
+        };
+
+        let mut sync = FileSync::new();
+        let diff = sync.update_files(items(&["b.rs", "a.rs"]), None);
+        assert_eq!(
+            diff,
+            SnapshotDiff {
+                added: 2,
+                removed: 0
+            }
+        );
+
+        let diff = sync.update_files(items(&["d.rs", "b.rs", "c.rs", "e.rs"]), None);
+        assert_eq!(
+            diff,
+            SnapshotDiff {
+                added: 3,
+                removed: 1
+            }
+        );
+
+        assert_eq!(
+            sync.update_files(items(&["b.rs", "c.rs", "d.rs", "e.rs"]), None),
+            SnapshotDiff::default()
+        );
+    }
+
+    #[test]
+    fn test_snapshot_history() {
+        let base = Path::new("/project");
+        let items = |paths: &[&str]| -> Vec<FileItem> {
+            paths
+                .iter()
+                .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+                .collect()
+        };
+
+        let mut sync = FileSync::new();
+        sync.update_files_with_history(items(&["a.rs", "b.rs", "c.rs"]), None, true);
+        let first_generation = sync.scan_generation;
+        sync.update_files_with_history(items(&["b.rs", "c.rs", "d.rs"]), None, true);
+        let second_generation = sync.scan_generation;
+        sync.update_files_with_history(items(&["c.rs", "d.rs", "e.rs"]), None, true);
+
+        // only the removed files are kept
+        let kept: Vec<usize> = sync
+            .snapshot_history
+            .iter()
+            .map(|removed| removed.files.len())
+            .collect();
+        assert_eq!(kept, [1, 1]);
+        assert_eq!(
+            sync.snapshot(None).as_ref(),
+            items(&["c.rs", "d.rs", "e.rs"])
+        );
+        // files created since are searched as well
+        assert_eq!(
+            sync.snapshot(Some(second_generation)).as_ref(),
+            items(&["b.rs", "c.rs", "d.rs", "e.rs"])
+        );
+        assert_eq!(
+            sync.snapshot(Some(first_generation)).as_ref(),
+            items(&["a.rs", "b.rs", "c.rs", "d.rs", "e.rs"])
+        );
+
+        // a removed file that was created again is only listed once
+        sync.update_files_with_history(items(&["a.rs", "e.rs"]), None, true);
+        sync.update_files_with_history(items(&["e.rs"]), None, true);
+        assert_eq!(sync.snapshot_history.len(), SNAPSHOT_HISTORY_CAPACITY);
+        // the removal of a.rs in the first update was dropped
+        assert_eq!(
+            sync.snapshot(Some(first_generation)).as_ref(),
+            items(&["a.rs", "b.rs", "c.rs", "d.rs", "e.rs"])
+        );
+        assert_eq!(
+            sync.snapshot(Some(sync.scan_generation - 1)).as_ref(),
+            items(&["a.rs", "e.rs"])
+        );
+    }
+
+    #[test]
+    fn test_navigation_stays_on_the_viewed_generation() {
+        let dir = TestDir::new("snapshot_navigation");
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let picker = dir.scanned_picker();
+        let paths = |picker: &FilePicker| -> Vec<String> {
+            let sync = read_sync_data(&picker.sync_data);
+            let files = sync.snapshot(picker.viewed_generation());
+            files
+                .iter()
+                .map(|file| file.relative_path.clone())
+                .collect()
+        };
+        assert!(!picker.navigate_to_previous_snapshot());
+
+        remove_paths_with_history(vec![dir.join("a.rs")], &picker.sync_data, &dir, true);
+        assert!(picker.navigate_to_previous_snapshot());
+        assert_eq!(paths(&picker), ["a.rs", "b.rs", "c.rs"]);
+
+        // a later removal does not move the viewed list
+        remove_paths_with_history(vec![dir.join("b.rs")], &picker.sync_data, &dir, true);
+        assert_eq!(paths(&picker), ["a.rs", "b.rs", "c.rs"]);
+        assert!(!picker.navigate_to_previous_snapshot());
+
+        picker.navigate_to_current();
+        assert_eq!(paths(&picker), ["c.rs"]);
+        assert!(picker.navigate_to_previous_snapshot());
+        assert_eq!(paths(&picker), ["b.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn test_stats_by_directory() {
+        let dir = TestDir::new("directory_stats");
+        let files = [
+            ("src/main.rs", "fn main() {}", Some(Status::WT_MODIFIED)),
+            ("src/api/user.rs", "struct User;", Some(Status::WT_NEW)),
+            ("src/lib.rs", "", Some(Status::INDEX_MODIFIED)),
+            ("docs/README.md", "# docs", None),
+            ("Cargo.toml", "[package]", None),
+        ];
+        let mut sync = FileSync::new();
+        sync.update_files(
+            files
+                .iter()
+                .map(|(path, content, status)| {
+                    let path = dir.join(path);
+                    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    std::fs::write(&path, content).unwrap();
+                    FileItem::new(path, &dir, *status).unwrap()
+                })
+                .collect(),
+            None,
+        );
+
+        let stats = sync.stats_by_directory();
+        let counts = |directory: &str| {
+            let stats = &stats[directory];
+            (
+                stats.file_count,
+                stats.total_size,
+                stats.modified_count,
+                stats.untracked_count,
+            )
+        };
+        assert_eq!(stats.len(), 3);
+        assert_eq!(counts("src"), (3, 24, 2, 1));
+        assert_eq!(counts("docs"), (1, 6, 0, 0));
+        assert_eq!(counts("."), (1, 9, 0, 0));
+    }
+
+    #[test]
+    fn test_batch_remove_paths() {
+        let base = Path::new("/project");
+        let mut sync = FileSync::new();
+        sync.update_files(
+            ["a.rs", "b.rs", "c.rs", "d.rs", "e.rs"]
+                .iter()
+                .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+                .collect(),
+            None,
+        );
+
+        assert_eq!(
+            sync.batch_remove_paths(&["e.rs", "missing.rs", "b.rs", "a.rs", "b.rs"]),
+            3
+        );
+        let remaining: Vec<&str> = sync
+            .files
+            .iter()
+            .map(|f| f.relative_path.as_str())
+            .collect();
+        assert_eq!(remaining, ["c.rs", "d.rs"]);
+        assert_eq!(sync.batch_remove_paths(&[]), 0);
+    }
+
+    #[test]
+    fn test_remove_of_unindexed_path_keeps_generation() {
+        let base = Path::new("/project");
+        let sync_data = Arc::new(RwLock::new(FileSync::new()));
+        write_sync_data(&sync_data).update_files(
+            ["a.rs", "src/b.rs"]
+                .iter()
+                .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+                .collect(),
+            None,
+        );
+        let generation = read_sync_data(&sync_data).scan_generation;
+
+        remove_paths_from_index(vec![base.join("missing.rs")], &sync_data, base);
+        assert_eq!(read_sync_data(&sync_data).scan_generation, generation);
+        assert!(read_sync_data(&sync_data).snapshot_history.is_empty());
+
+        remove_paths_with_history(vec![base.join("src")], &sync_data, base, true);
+        let sync = read_sync_data(&sync_data);
+        assert_ne!(sync.scan_generation, generation);
+        assert_eq!(sync.snapshot_history.len(), 1);
+        assert_eq!(sync.snapshot_history[0].generation, generation);
+        assert_eq!(sync.snapshot_history[0].files[0].relative_path, "src/b.rs");
+        assert_eq!(sync.files.len(), 1);
+    }
+
+    #[test]
+    fn test_sequential_and_parallel_matching_agree() {
+        let base = Path::new("/project");
+        let files: Vec<FileItem> = (0..200)
+            .map(|i| {
+                FileItem::new(
+                    base.join(format!("src/module_{}/file_{}.rs", i % 9, i)),
+                    base,
+                    None,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        for query in ["fi", "file_1", "mod7 fl"] {
+            let matches = |parallel_threshold| {
+                let mut matches = crate::score::match_and_score_files(
+                    &files,
+                    &ScoringContext {
+                        max_threads: 4,
+                        parallel_threshold,
+                        ..test_scoring_context(query)
+                    },
+                );
+                matches.sort_by_key(|(idx, score)| (score.total, *idx));
+                matches
+                    .into_iter()
+                    .map(|(idx, score)| (idx, score.total))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(matches(usize::MAX), matches(0), "query {}", query);
+        }
+    }
+
+    #[test]
+    fn test_frecency_weights_change_ranking_without_query() {
+        let base = Path::new("/project");
+        let mut opened = FileItem::new(base.join("opened.rs"), base, None).unwrap();
+        opened.access_frecency_score = 30;
+        let mut edited = FileItem::new(base.join("edited.rs"), base, None).unwrap();
+        edited.modification_frecency_score = 10;
+        let files = [opened, edited];
+
+        let ranking = |context: &ScoringContext| -> Vec<String> {
+            let mut scores = crate::score::match_and_score_files(&files, context);
+            scores.sort_by(|a, b| b.1.total.cmp(&a.1.total));
+            scores
+                .into_iter()
+                .map(|(idx, _)| files[idx].relative_path.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            ranking(&test_scoring_context("")),
+            ["edited.rs", "opened.rs"]
+        );
+        let access_heavy = ScoringContext {
+            frecency_weight_access: 2,
+            frecency_weight_modification: 1,
+            ..test_scoring_context("")
+        };
+        assert_eq!(ranking(&access_heavy), ["opened.rs", "edited.rs"]);
+    }
+
+    #[test]
+    fn test_extreme_frecency_scores_saturate() {
+        let base = Path::new("/project");
+        let score_with_frecency = |frecency: i64| {
+            let mut file = FileItem::new(base.join("src/main.rs"), base, None).unwrap();
+            file.access_frecency_score = frecency;
+            file.modification_frecency_score = frecency;
+            file.total_frecency_score = frecency;
+            file.parent_directory_frecency = frecency;
+            let files = [file];
+            let scores = |query| {
+                let context = ScoringContext {
+                    branch_change_bonus: i32::MAX,
+                    config_file_penalty: i32::MIN,
+                    hidden_file_penalty: i32::MIN,
+                    directory_frecency_weight: f64::MAX,
+                    frecency_weight_access: i32::MAX,
+                    frecency_weight_modification: i32::MAX,
+                    ..test_scoring_context(query)
+                };
+                crate::score::match_and_score_files(&files, &context)
+            };
+            (scores("main")[0].1.total, scores("")[0].1.total)
+        };
+
+        let (matched, unmatched) = score_with_frecency(0);
+        for frecency in [i64::MAX, i64::from(i32::MAX) + 1, 1 << 40] {
+            let (extreme_matched, extreme_unmatched) = score_with_frecency(frecency);
+            assert!(extreme_matched >= matched, "frecency {}", frecency);
+            assert!(extreme_unmatched >= unmatched, "frecency {}", frecency);
+        }
+        for frecency in [i64::MIN, -(1 << 40)] {
+            let (extreme_matched, extreme_unmatched) = score_with_frecency(frecency);
+            assert!(extreme_matched <= matched, "frecency {}", frecency);
+            assert!(extreme_unmatched <= unmatched, "frecency {}", frecency);
+        }
+    }
+
+    #[test]
+    fn test_find_file_by_inode() {
+        let base = Path::new("/project");
+        let item = |path: &str, inode: u64| {
+            let mut file = FileItem::new(base.join(path), base, None).unwrap();
+            file.inode = inode;
+            file
+        };
+        let mut sync = FileSync::new();
+        sync.update_files(
+            vec![item("c.rs", 30), item("a.rs", 10), item("e.rs", 0)],
+            None,
+        );
+        let path_of = |sync: &FileSync, inode| {
+            sync.find_file_by_inode(inode)
+                .map(|file| file.relative_path.clone())
+        };
+        assert_eq!(path_of(&sync, 30).as_deref(), Some("c.rs"));
+        assert_eq!(path_of(&sync, 0), None);
+
+        // inserting before indexed files shifts their positions
+        sync.insert_file_sorted(item("b.rs", 20));
+        assert_eq!(path_of(&sync, 10).as_deref(), Some("a.rs"));
+        assert_eq!(path_of(&sync, 20).as_deref(), Some("b.rs"));
+        assert_eq!(path_of(&sync, 30).as_deref(), Some("c.rs"));
+
+        sync.batch_remove_paths(&["a.rs"]);
+        assert_eq!(path_of(&sync, 10), None);
+        assert_eq!(path_of(&sync, 30).as_deref(), Some("c.rs"));
+
+        // nothing indexed, as after loading a cache written without inodes
+        sync.inode_index.clear();
+        assert_eq!(path_of(&sync, 20).as_deref(), Some("b.rs"));
+    }
+
+    #[test]
+    fn test_create_event_of_directory_indexes_its_files() {
+        let dir = TestDir::new("create_directory");
+        std::fs::create_dir_all(dir.join("src/api/v1")).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        let sync_data = Arc::new(RwLock::new(FileSync::new()));
+        write_sync_data(&sync_data).update_files(scan_filesystem(&dir, None).unwrap().files, None);
+
+        std::fs::write(dir.join("src/api/user.rs"), "").unwrap();
+        std::fs::write(dir.join("src/api/v1/routes.rs"), "").unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        let generation = read_sync_data(&sync_data).scan_generation;
+        // the file events of the directory's files arrive along with it
+        handle_create_events(
+            &[dir.join("src"), dir.join("src/lib.rs")],
+            &sync_data,
+            &dir,
+            None,
+            &RepositoryCache::default(),
+        );
+
+        let sync = read_sync_data(&sync_data);
+        let paths: Vec<&str> = sync
+            .files
+            .iter()
+            .map(|file| file.relative_path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "main.rs",
+                "src/api/user.rs",
+                "src/api/v1/routes.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert_ne!(sync.scan_generation, generation);
+        drop(sync);
+
+        // nothing new under an already indexed directory
+        let generation = read_sync_data(&sync_data).scan_generation;
+        let inserted = write_sync_data(&sync_data).insert_files(collect_directory_files(
+            &dir.join("src"),
+            &dir,
+            None,
+            &RepositoryCache::default(),
+        ));
+        assert_eq!(inserted, 0);
+        assert_eq!(read_sync_data(&sync_data).scan_generation, generation);
+    }
+
+    #[test]
+    fn test_create_events_wait_for_the_repository_without_the_write_lock() {
+        let dir = TestDir::new("create_without_write_lock");
+        Repository::init(&dir).unwrap();
+        let sync_data = Arc::new(RwLock::new(FileSync::new()));
+        let repository_cache = RepositoryCache::default();
+        std::fs::write(dir.join("new.rs"), "").unwrap();
+
+        thread::scope(|s| {
+            // stands in for a git status refresh holding the repository
+            let repository = repository_cache.0.lock().unwrap();
+            let handler = s.spawn(|| {
+                handle_create_events(
+                    &[dir.join("new.rs")],
+                    &sync_data,
+                    &dir,
+                    Some(&dir.to_path_buf()),
+                    &repository_cache,
+                )
+            });
+
+            thread::sleep(Duration::from_millis(100));
+            assert!(sync_data.try_write().is_ok());
+            drop(repository);
+            handler.join().unwrap();
+        });
+
+        assert!(read_sync_data(&sync_data).contains_path("new.rs"));
+    }
+
+    #[test]
+    fn test_created_directory_skips_submodules() {
+        let dir = TestDir::new("create_directory_submodule");
+        Repository::init(&dir).unwrap();
+        std::fs::write(
+            dir.join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = https://example.com/lib.git\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("vendor/lib")).unwrap();
+        std::fs::write(dir.join("vendor/lib/lib.rs"), "").unwrap();
+        std::fs::write(dir.join("vendor/mod.rs"), "").unwrap();
+
+        let repository_cache = RepositoryCache::default();
+        let relative_paths = |dir_path: &Path| -> Vec<String> {
+            collect_directory_files(dir_path, &dir, Some(&dir), &repository_cache)
+                .into_iter()
+                .map(|file| file.relative_path)
+                .collect()
+        };
+        assert_eq!(relative_paths(&dir.join("vendor")), ["vendor/mod.rs"]);
+        assert!(relative_paths(&dir.join("vendor/lib")).is_empty());
+        // the submodules are read from the watcher's repository
+        assert!(repository_cache.0.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_file_created_in_empty_directory_is_indexed() {
+        let dir = TestDir::new("create_in_empty_directory");
+        Repository::init(&dir).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        let sync_data = Arc::new(RwLock::new(FileSync::new()));
+        write_sync_data(&sync_data)
+            .update_files(scan_filesystem(&dir, Some(&dir)).unwrap().files, None);
+
+        let create = |path: PathBuf| {
+            DebouncedEvent::new(
+                notify::Event::new(EventKind::Create(notify::event::CreateKind::Any))
+                    .add_path(path),
+                std::time::Instant::now(),
+            )
+        };
+        let handle = |events| {
+            handle_debounced_events(
+                events,
+                &sync_data,
+                &dir,
+                &Some(dir.to_path_buf()),
+                &RepositoryCache::default(),
+            )
+        };
+
+        std::fs::create_dir_all(dir.join("empty_dir")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        handle(vec![
+            create(dir.join("empty_dir")),
+            create(dir.join("target")),
+        ]);
+
+        // the files arrive in a later batch than their directories
+        std::fs::write(dir.join("empty_dir/a.rs"), "").unwrap();
+        std::fs::write(dir.join("target/build.rs"), "").unwrap();
+        handle(vec![
+            create(dir.join("empty_dir/a.rs")),
+            create(dir.join("target/build.rs")),
+        ]);
+
+        let sync = read_sync_data(&sync_data);
+        let paths: Vec<&str> = sync
+            .files
+            .iter()
+            .map(|file| file.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, [".gitignore", "empty_dir/a.rs", "main.rs"]);
+    }
+
+    #[test]
+    fn test_extension_filters() {
+        let set = |extensions: &[&str]| -> HashSet<String> {
+            extensions.iter().map(|ext| ext.to_string()).collect()
+        };
+        let none = HashSet::new();
+
+        assert!(extension_passes_filters("rs", &none, &none));
+        assert!(!extension_passes_filters("LOG", &none, &set(&["log"])));
+        assert!(!extension_passes_filters("md", &set(&["rs"]), &none));
+        // the allowlist takes priority
+        assert!(extension_passes_filters("rs", &set(&["rs"]), &set(&["rs"])));
+    }
+
+    #[test]
+    fn test_excluded_extensions_are_not_indexed() {
+        // the filter is global, so the extension is one no other test uses
+        let dir = TestDir::new("excluded_extensions");
+        for path in ["main.rs", "data.fffexcluded", "DATA.FFFEXCLUDED"] {
+            std::fs::write(dir.join(path), "").unwrap();
+        }
+        EXCLUDED_EXTENSIONS
+            .write()
+            .unwrap()
+            .insert("fffexcluded".to_string());
+
+        let scan = scan_filesystem(&dir, None).unwrap();
+        std::fs::write(dir.join("late.fffexcluded"), "").unwrap();
+        let add_late_file = should_add_new_file(
+            &dir.join("late.fffexcluded"),
+            &dir,
+            None,
+            &RepositoryCache::default(),
+        );
+        EXCLUDED_EXTENSIONS.write().unwrap().remove("fffexcluded");
+
+        let paths: Vec<&str> = scan
+            .files
+            .iter()
+            .map(|file| file.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, ["main.rs"]);
+        assert_eq!(scan.skipped_file_count, 2);
+        assert!(!add_late_file);
+    }
+
+    #[test]
+    fn test_scan_skips_resource_forks() {
+        let dir = TestDir::new("resource_forks");
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("._main.rs"), [0u8, 5, 22, 7]).unwrap();
+
+        let files = scan_filesystem(&dir, None).unwrap().files;
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].relative_path, "main.rs");
+        assert!(!should_add_new_file(
+            &dir.join("._main.rs"),
+            &dir,
+            None,
+            &RepositoryCache::default()
+        ));
+    }
+
+    #[test]
+    fn test_validate_base_path() {
+        let dir = TestDir::new("validate_base");
+        std::fs::write(dir.join("file.rs"), "").unwrap();
+
+        assert!(validate_base_path(&dir).is_ok());
+        assert!(matches!(
+            validate_base_path(&dir.join("missing")),
+            Err(Error::InvalidPath(_))
+        ));
+        assert!(matches!(
+            FilePicker::with_file_cache(dir.join("file.rs").to_string_lossy().into_owned(), None),
+            Err(Error::NotADirectory(path)) if path == dir.join("file.rs")
+        ));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+            let error = validate_base_path(&dir.join("dangling")).unwrap_err();
+            assert!(matches!(error, Error::BrokenSymlink(_)));
+            assert!(error.to_string().contains("symlink"));
+        }
+    }
+
+    #[test]
+    fn test_compact_snapshot() {
+        let base = Path::new("/project");
+        let mut sync = FileSync::new();
+        sync.update_files(
+            [
+                ("a.rs", 5),
+                ("b.rs", 0),
+                ("c.rs", 9),
+                ("d.rs", 2),
+                ("e.rs", 7),
+            ]
+            .iter()
+            .map(|(path, score)| {
+                let mut file = FileItem::new(base.join(path), base, None).unwrap();
+                file.total_frecency_score = *score;
+                file
+            })
+            .collect(),
+            None,
+        );
+
+        assert_eq!(sync.compact_snapshot(1, None), 1);
+        assert_eq!(sync.compact_snapshot(0, Some(2)), 2);
+        let remaining: Vec<&str> = sync
+            .files
+            .iter()
+            .map(|f| f.relative_path.as_str())
+            .collect();
+        assert_eq!(remaining, ["c.rs", "e.rs"]);
+        assert_eq!(sync.compact_snapshot(0, Some(10)), 0);
+    }
+
+    #[test]
+    fn test_collect_top_results_keeps_input_order_on_ties() {
+        let base = Path::new("/project");
+        let files: Vec<FileItem> = (0..2000)
+            .map(|i| FileItem::new(base.join(format!("file_{:04}.rs", i)), base, None).unwrap())
+            .collect();
+        let scored_indices = (0..files.len())
+            .map(|idx| {
+                let score = Score {
+                    total: 0,
+                    base_score: 0,
+                    filename_bonus: 0,
+                    special_filename_bonus: 0,
+                    frecency_boost: 0,
+                    distance_penalty: 0,
+                    similarity_bonus: 0,
+                    branch_change_bonus: 0,
+                    relation_bonus: 0,
+                    match_type: "frecency",
+                    confidence: 0.0,
+                };
+                (idx, score)
+            })
+            .collect();
+
+        let (items, _) = collect_top_results(&files, scored_indices, 100);
+        assert_eq!(items, files[..100]);
+    }
+
+    #[test]
+    fn test_collect_top_results_prefers_shorter_paths_on_ties() {
+        let base = Path::new("/project");
+        let files: Vec<FileItem> = ["very/deeply/nested/src/lib.rs", "src/lib.rs"]
+            .iter()
+            .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+            .collect();
+        let score = Score {
+            total: 100,
+            base_score: 100,
+            filename_bonus: 0,
+            special_filename_bonus: 0,
+            frecency_boost: 0,
+            distance_penalty: 0,
+            similarity_bonus: 0,
+            branch_change_bonus: 0,
+            relation_bonus: 0,
+            match_type: "fuzzy_filename",
+            confidence: 0.4,
+        };
+        let scored_indices = vec![(0, score.clone()), (1, score)];
+
+        let (items, _) = collect_top_results(&files, scored_indices, 10);
+        let paths: Vec<&str> = items.iter().map(|f| f.relative_path.as_str()).collect();
+        assert_eq!(paths, ["src/lib.rs", "very/deeply/nested/src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_chunked_search_decides_fallback_for_all_files() {
+        let base = Path::new("/project");
+        let files = |paths: &[&str]| -> Vec<FileItem> {
+            paths
+                .iter()
+                .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+                .collect()
+        };
+        let matched_paths = |files: &[FileItem]| -> Vec<(String, &str)> {
+            let context = test_scoring_context("buttqqqon");
+            let deadline = std::time::Instant::now() + Duration::from_secs(60);
+            let (scored, is_partial) =
+                search_in_chunks(files, SearchMode::Fuzzy, "buttqqqon", &context, 1, deadline);
+            assert!(!is_partial);
+            scored
+                .into_iter()
+                .map(|(idx, score)| (files[idx].relative_path.clone(), score.match_type))
+                .collect()
+        };
+
+        // the chunk of button.tsx has no strict match, but the search as a whole does
+        assert_eq!(
+            matched_paths(&files(&["src/buttqqqon.tsx", "src/components/button.tsx"])),
+            [("src/buttqqqon.tsx".to_string(), "fuzzy_filename")]
+        );
+        assert_eq!(
+            matched_paths(&files(&["README.md", "src/components/button.tsx"])),
+            [("src/components/button.tsx".to_string(), "permissive_fuzzy")]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_search_prefers_shorter_paths() {
+        let dir = TestDir::new("shorter_paths");
+        for path in ["very/deeply/nested/src/lib.rs", "src/lib.rs"] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        // both score the same, the shorter path wins although the other one is more recent
+        std::fs::File::options()
+            .write(true)
+            .open(dir.join("src/lib.rs"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+
+        let picker = dir.scanned_picker();
+        let result = picker.fuzzy_search("lib", 10, 1, None, None);
+        let paths: Vec<String> = result
+            .items
+            .iter()
+            .map(|file| file.relative_path.replace('\\', "/"))
+            .collect();
+        assert_eq!(paths, ["src/lib.rs", "very/deeply/nested/src/lib.rs"]);
+    }
+}
+
+/// Timing benchmarks on synthetic trees, ignored by default since they take a while:
+/// `cargo test --release --lib benches -- --ignored --nocapture --test-threads 1`
+#[cfg(test)]
+mod benches {
+    use super::*;
+
+    const FILE_COUNTS: [usize; 3] = [1_000, 10_000, 50_000];
+    const QUERIES: [&str; 10] = [
+        "main",
+        "mod",
+        "file_42",
+        "src/module",
+        "sub3 file",
+        "rs",
+        "modl_1",
+        "file_9999",
+        "module_10/sub_2",
+        "xyzzy",
+    ];
+
+    struct SyntheticTree {
+        path: super::tests::TestDir,
+    }
+
+    impl SyntheticTree {
+        fn new(file_count: usize) -> Self {
+            let path = super::tests::TestDir::new(&format!("bench_{}", file_count));
+            for i in 0..file_count {
+                let dir = path.join(format!("src/module_{}/sub_{}", i % 50, i % 7));
+                std::fs::create_dir_all(&dir).unwrap();
+                let extension = ["rs", "ts", "lua", "md"][i % 4];
+                std::fs::write(dir.join(format!("file_{}.{}", i, extension)), "content\n").unwrap();
+            }
+            Self { path }
+        }
+
+        fn file_sync(&self) -> FileSync {
+            let mut sync = FileSync::new();
+            sync.update_files(scan_filesystem(&self.path, None).unwrap().files, None);
+            sync
+        }
+    }
+
+    /// Prints the median duration of `iterations` runs of `f`
+    fn bench(name: &str, iterations: usize, mut f: impl FnMut()) {
+        let mut timings: Vec<Duration> = (0..iterations)
+            .map(|_| {
+                let start = std::time::Instant::now();
+                f();
+                start.elapsed()
+            })
+            .collect();
+        timings.sort_unstable();
+        println!("{:<50} {:>12?}", name, timings[timings.len() / 2]);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_scan_filesystem() {
+        for file_count in FILE_COUNTS {
+            let tree = SyntheticTree::new(file_count);
+            bench(&format!("scan_filesystem/{}", file_count), 5, || {
+                scan_filesystem(&tree.path, None).unwrap();
+            });
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_fuzzy_search() {
+        for file_count in FILE_COUNTS {
+            let tree = SyntheticTree::new(file_count);
+            let picker = tree.path.scanned_picker();
+
+            for query in QUERIES {
+                bench(
+                    &format!("fuzzy_search/{}/{}", file_count, query),
+                    10,
+                    || {
+                        picker.fuzzy_search(query, 50, 4, None, None);
+                    },
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_parallel_matching() {
+        for file_count in [100, 500, 1_000, 5_000, 50_000] {
+            let tree = SyntheticTree::new(file_count);
+            let files = tree.file_sync().files;
+            for query in ["src/module", "modl_1", "file_9999"] {
+                for (mode, parallel_threshold) in [("sequential", usize::MAX), ("parallel", 0)] {
+                    let context = ScoringContext {
+                        max_threads: 4,
+                        parallel_threshold,
+                        ..crate::score::tests::test_scoring_context(query)
+                    };
+                    bench(
+                        &format!("match/{}/{}/{}", mode, file_count, query),
+                        10,
+                        || {
+                            crate::score::match_and_score_files(&files, &context);
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_filename_similarity() {
+        use crate::path_utils::{calculate_filename_similarity_bonus, SimilarityAlgorithm};
+
+        // 90% of the candidates share the current stem's leading letters but little else
+        let current_stem = "user_service";
+        let candidates: Vec<String> = (0..50_000)
+            .map(|i| match i % 10 {
+                0 => format!("user_service_{}", i),
+                _ => format!("uxkdq_pm{}", i),
+            })
+            .collect();
+
+        let algorithm = SimilarityAlgorithm::JaroWinkler;
+        bench("similarity/metric_only/50000", 10, || {
+            for candidate in &candidates {
+                std::hint::black_box(algorithm.similarity(current_stem, candidate));
+            }
+        });
+        bench("similarity/with_fast_reject/50000", 10, || {
+            for candidate in &candidates {
+                std::hint::black_box(calculate_filename_similarity_bonus(
+                    current_stem,
+                    candidate,
+                    algorithm,
+                ));
+            }
+        });
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_concurrent_fuzzy_search() {
+        let tree = SyntheticTree::new(50_000);
+        let picker = tree.path.scanned_picker();
+
+        for reader_count in [1, 4] {
+            bench(
+                &format!("concurrent_fuzzy_search/50000/{}_readers", reader_count),
+                5,
+                || {
+                    thread::scope(|s| {
+                        for _ in 0..reader_count {
+                            s.spawn(|| {
+                                for query in QUERIES {
+                                    picker.fuzzy_search(query, 50, 1, None, None);
+                                }
+                            });
+                        }
+                    });
+                },
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_batch_update_frecency_scores() {
+        let db_path = std::env::temp_dir().join(format!("fff_bench_db_{}", std::process::id()));
+        let tracker = FrecencyTracker::new(&db_path.to_string_lossy(), true).unwrap();
+        *FRECENCY.write().unwrap() = Some(tracker);
+
+        for file_count in FILE_COUNTS {
+            let tree = SyntheticTree::new(file_count);
+            let mut sync = tree.file_sync();
+            bench(
+                &format!("batch_update_frecency_scores/{}", file_count),
+                5,
+                || {
+                    // force a full update instead of only the recently accessed files
+                    sync.last_frecency_update = 0;
+                    sync.batch_update_frecency_scores();
+                },
+            );
+        }
+
+        *FRECENCY.write().unwrap() = None;
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
+    /// Distance penalties splitting each candidate directory while scoring, against
+    /// directories split once per file ahead of the search
+    #[test]
+    #[ignore]
+    fn bench_candidate_directory_components() {
+        use crate::path_utils::{calculate_directory_distance_penalty, split_path_components};
+
+        let tree = SyntheticTree::new(10_000);
+        let files = tree.file_sync().files;
+        let current_parts = ["src", "module_25", "sub_3"];
+
+        bench("distance_penalty/split_while_scoring/10000", 20, || {
+            for file in &files {
+                std::hint::black_box(calculate_directory_distance_penalty(
+                    &current_parts,
+                    &file.directory,
+                ));
+            }
+        });
+
+        let precompute = || -> Vec<Vec<String>> {
+            files
+                .iter()
+                .map(|file| {
+                    split_path_components(&file.directory)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .collect()
+        };
+        bench("distance_penalty/precompute_components/10000", 20, || {
+            std::hint::black_box(precompute());
+        });
+        let candidate_dirs = precompute();
+        bench("distance_penalty/precomputed_components/10000", 20, || {
+            for candidate_parts in &candidate_dirs {
+                let common_len = current_parts
+                    .iter()
+                    .zip(candidate_parts)
+                    .take_while(|(a, b)| **a == b.as_str())
+                    .count();
+                let distance = current_parts.len() + candidate_parts.len() - 2 * common_len;
+                std::hint::black_box(-2 * distance.min(10) as i32);
+            }
+        });
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_insert_file_sorted() {
+        for file_count in FILE_COUNTS {
+            let tree = SyntheticTree::new(file_count);
+            let mut sync = tree.file_sync();
+            let new_file =
+                FileItem::new(tree.path.join("src/module_25/new.rs"), &tree.path, None).unwrap();
+            bench(&format!("insert_file_sorted/{}", file_count), 100, || {
+                sync.insert_file_sorted(new_file.clone());
+                sync.batch_remove_paths(&[new_file.relative_path.as_str()]);
+            });
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_git_status_lookup() {
+        const CHANGED_FILES: usize = 10_000;
+        const LOOKUPS: usize = 100_000;
+
+        // one lookup per indexed file, every tenth file is changed
+        let base = Path::new("/home/user/project");
+        let lookup_paths: Vec<PathBuf> = (0..LOOKUPS)
+            .map(|i| base.join(format!("src/module_{}/sub_{}/file_{}.rs", i % 50, i % 7, i)))
+            .collect();
+        let entries: Vec<(PathBuf, Status)> = lookup_paths
+            .iter()
+            .step_by(LOOKUPS / CHANGED_FILES)
+            .map(|path| (path.clone(), Status::WT_MODIFIED))
+            .collect();
+
+        bench("git_status/sorted_vecs/build", 20, || {
+            let mut sorted = entries.clone();
+            sorted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            let (paths, statuses): (Vec<PathBuf>, Vec<Status>) = sorted.into_iter().unzip();
+            std::hint::black_box((paths, statuses));
+        });
+        let mut sorted = entries.clone();
+        sorted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let (paths, statuses): (Vec<PathBuf>, Vec<Status>) = sorted.into_iter().unzip();
+        bench("git_status/sorted_vecs/lookups", 20, || {
+            for path in &lookup_paths {
+                let status = paths
+                    .binary_search_by(|probe| probe.as_path().cmp(path))
+                    .ok()
+                    .map(|index| statuses[index]);
+                std::hint::black_box(status);
+            }
+        });
+
+        bench("git_status/hash_map/build", 20, || {
+            std::hint::black_box(GitStatusCache::from_git_entries(entries.clone(), false));
+        });
+        let cache = GitStatusCache::from_git_entries(entries.clone(), false);
+        bench("git_status/hash_map/lookups", 20, || {
+            for path in &lookup_paths {
+                std::hint::black_box(cache.lookup_status(path));
+            }
+        });
     }
 }
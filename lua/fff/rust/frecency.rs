@@ -1,24 +1,72 @@
 use crate::error::Error;
 use crate::file_key::FileKey;
+use crate::types::SearchHistoryEntry;
 use heed::{
-    types::{Bytes, SerdeBincode},
+    types::{Bytes, SerdeBincode, Str, Unit},
     EnvFlags,
 };
 use heed::{Database, Env, EnvOpenOptions};
-use std::collections::VecDeque;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use std::collections::{HashSet, VecDeque};
 use std::fs;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, PoisonError, RwLock,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const DECAY_CONSTANT: f64 = 0.0693; // ln(2)/10 for 10-day half-life
 const SECONDS_PER_DAY: f64 = 86400.0;
 const MAX_HISTORY_DAYS: f64 = 30.0; // Only consider accesses within 30 days
 
-#[derive(Debug)]
 pub struct FrecencyTracker {
     env: Env,
     db: Database<Bytes, SerdeBincode<VecDeque<u64>>>,
+    /// Key hash to path, written in the same transaction as the access list
+    paths_db: Database<Bytes, Str>,
+    /// `project root, NUL, key hash` for every relative path tracked in a project, the records
+    /// themselves are shared by all projects
+    projects_db: Database<Bytes, Unit>,
+    /// `last access (big endian unix seconds), key hash` for every record, so the recently
+    /// accessed files are found without reading all access lists
+    recent_db: Database<Bytes, Unit>,
+    /// Project root to its search selections, oldest first
+    history_db: Database<Str, SerdeBincode<Vec<SearchHistoryEntry>>>,
+    /// Unix milliseconds of the last write committed by this tracker
+    last_local_write: Arc<AtomicU64>,
+    /// Set when another process wrote to the database, cleared by `take_external_modification`
+    externally_modified: Arc<AtomicBool>,
+    _db_watcher: Option<Debouncer<RecommendedWatcher, RecommendedCache>>,
 }
 
+impl std::fmt::Debug for FrecencyTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrecencyTracker")
+            .field("env", &self.env)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Upper bound on the number of paths returned by `all_paths`
+const MAX_TRACKED_PATHS: usize = 10_000;
+
+/// Writes of other processes closer together than this are reported once
+const EXTERNAL_CHANGE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// LMDB data file inside the environment directory
+const DB_DATA_FILE: &str = "data.mdb";
+
+/// Named databases stored next to the access lists, which stay in the unnamed database. Their
+/// names are keys of the unnamed database, so it must only be read by key hash, never iterated.
+const PATHS_DB: &str = "paths";
+const RECENT_DB: &str = "recent";
+const PROJECTS_DB: &str = "projects";
+const SEARCH_HISTORY_DB: &str = "search_history";
+const NAMED_DATABASES: u32 = 4;
+
 const ACCESS_THRESHOLDS: [(i64, u64); 5] = [
     (12, 60 * 2),          // 2 minutes
     (6, 60 * 10),          // 10 minutes
@@ -29,9 +77,109 @@ const ACCESS_THRESHOLDS: [(i64, u64); 5] = [
 
 impl FrecencyTracker {
     pub fn new(db_path: &str, use_unsafe_no_lock: bool) -> Result<Self, Error> {
+        let env = Self::open_env(db_path, use_unsafe_no_lock)?;
+
+        // access lists live in the default unnamed database, everything else in named ones
+        let mut wtxn = env.write_txn().map_err(Error::DbStartWriteTxn)?;
+        let db = env
+            .create_database(&mut wtxn, None)
+            .map_err(Error::DbCreate)?;
+        let paths_db = env
+            .create_database(&mut wtxn, Some(PATHS_DB))
+            .map_err(Error::DbCreate)?;
+        let recent_db = env
+            .create_database(&mut wtxn, Some(RECENT_DB))
+            .map_err(Error::DbCreate)?;
+        let projects_db = env
+            .create_database(&mut wtxn, Some(PROJECTS_DB))
+            .map_err(Error::DbCreate)?;
+        let history_db = env
+            .create_database(&mut wtxn, Some(SEARCH_HISTORY_DB))
+            .map_err(Error::DbCreate)?;
+        wtxn.commit().map_err(Error::DbCommit)?;
+
+        let last_local_write = Arc::new(AtomicU64::new(unix_millis(SystemTime::now())));
+        let externally_modified = Arc::new(AtomicBool::new(false));
+        let db_watcher = Self::watch_for_external_changes(
+            Path::new(db_path),
+            &last_local_write,
+            &externally_modified,
+        );
+
+        Ok(FrecencyTracker {
+            db,
+            env: env.clone(),
+            paths_db,
+            projects_db,
+            recent_db,
+            history_db,
+            last_local_write,
+            externally_modified,
+            _db_watcher: db_watcher,
+        })
+    }
+
+    /// Flags writes of other processes, e.g. another Neovim instance sharing the database,
+    /// by comparing the data file's mtime with the last local commit. Reads always see the
+    /// latest committed data since no scores are cached here, so there is nothing to reload.
+    fn watch_for_external_changes(
+        db_path: &Path,
+        last_local_write: &Arc<AtomicU64>,
+        externally_modified: &Arc<AtomicBool>,
+    ) -> Option<Debouncer<RecommendedWatcher, RecommendedCache>> {
+        let data_file = db_path.join(DB_DATA_FILE);
+        let mut debouncer = new_debouncer(EXTERNAL_CHANGE_DEBOUNCE, None, {
+            let last_local_write = Arc::clone(last_local_write);
+            let externally_modified = Arc::clone(externally_modified);
+
+            move |result: DebounceEventResult| {
+                let Ok(events) = result else {
+                    return;
+                };
+
+                let data_file_changed = events
+                    .iter()
+                    .filter(|event| !matches!(event.kind, EventKind::Access(_)))
+                    .flat_map(|event| &event.paths)
+                    .any(|path| path.file_name().is_some_and(|name| name == DB_DATA_FILE));
+                if !data_file_changed {
+                    return;
+                }
+
+                let Ok(modified) = fs::metadata(&data_file).and_then(|m| m.modified()) else {
+                    return;
+                };
+                if unix_millis(modified) > last_local_write.load(Ordering::Relaxed) {
+                    tracing::debug!("Frecency database was modified by another process");
+                    externally_modified.store(true, Ordering::Relaxed);
+                }
+            }
+        })
+        .map_err(|e| tracing::warn!("Failed to create frecency database watcher: {:?}", e))
+        .ok()?;
+
+        debouncer
+            .watch(db_path, RecursiveMode::NonRecursive)
+            .map_err(|e| tracing::warn!("Failed to watch frecency database: {:?}", e))
+            .ok()?;
+        Some(debouncer)
+    }
+
+    /// Whether another process wrote to the database since the last call
+    pub fn take_external_modification(&self) -> bool {
+        self.externally_modified.swap(false, Ordering::Relaxed)
+    }
+
+    fn record_local_write(&self) {
+        self.last_local_write
+            .store(unix_millis(SystemTime::now()), Ordering::Relaxed);
+    }
+
+    fn open_env(db_path: &str, use_unsafe_no_lock: bool) -> Result<Env, Error> {
         fs::create_dir_all(db_path).map_err(Error::CreateDir)?;
         let env = unsafe {
             let mut opts = EnvOpenOptions::new();
+            opts.max_dbs(NAMED_DATABASES);
             if use_unsafe_no_lock {
                 opts.flags(EnvFlags::NO_LOCK | EnvFlags::NO_SYNC | EnvFlags::NO_META_SYNC);
             }
@@ -39,17 +187,29 @@ impl FrecencyTracker {
         };
         env.clear_stale_readers()
             .map_err(Error::DbClearStaleReaders)?;
+        Ok(env)
+    }
 
-        // we will open the default unnamed database
-        let mut wtxn = env.write_txn().map_err(Error::DbStartWriteTxn)?;
-        let db = env
-            .create_database(&mut wtxn, None)
-            .map_err(Error::DbCreate)?;
+    /// Whether the database can still be read
+    fn is_healthy(&self) -> bool {
+        self.env
+            .read_txn()
+            .is_ok_and(|rtxn| self.db.len(&rtxn).is_ok())
+    }
 
-        Ok(FrecencyTracker {
-            db,
-            env: env.clone(),
-        })
+    /// Clears the poison of a lock left behind by a panicking holder. The tracker is kept when
+    /// its database is still readable and dropped otherwise, returns whether it was kept.
+    pub fn try_recover(lock: &RwLock<Option<Self>>) -> bool {
+        let mut tracker = lock.write().unwrap_or_else(PoisonError::into_inner);
+        let healthy = tracker.as_ref().is_none_or(Self::is_healthy);
+        if !healthy {
+            tracing::error!("Frecency database is unreadable after a panic, disabling frecency");
+            *tracker = None;
+        }
+
+        drop(tracker);
+        lock.clear_poison();
+        healthy
     }
 
     fn get_accesses(&self, file_key: &FileKey) -> Result<Option<VecDeque<u64>>, Error> {
@@ -65,15 +225,40 @@ impl FrecencyTracker {
             .as_secs()
     }
 
-    fn path_to_hash_bytes(path: &str) -> [u8; 32] {
+    pub fn path_to_hash_bytes(path: &str) -> [u8; 32] {
         *blake3::hash(path.as_bytes()).as_bytes()
     }
 
-    pub fn track_access(&self, file_key: &FileKey) -> Result<(), Error> {
+    /// Returns the key hashes of every file accessed at or after `since` (unix seconds). Only
+    /// the index entries from `since` on are read. Accesses tracked before the index existed
+    /// are not found, they are older than any `since` of a running picker.
+    pub fn get_accesses_since(&self, since: u64) -> Result<HashSet<[u8; 32]>, Error> {
+        let rtxn = self.env.read_txn().map_err(Error::DbStartReadTxn)?;
+        let since = since.to_be_bytes();
+        let range = (Bound::Included(&since[..]), Bound::Unbounded);
+        let mut keys = HashSet::new();
+        for entry in self.recent_db.range(&rtxn, &range).map_err(Error::DbRead)? {
+            let (key, ()) = entry.map_err(Error::DbRead)?;
+            if let Some(hash) = key_hash_of(key) {
+                keys.insert(hash);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Records an access of `file_key`, relative paths are remembered as part of the project at
+    /// `project_root` for `prune_for_workspace`
+    pub fn track_access(
+        &self,
+        file_key: &FileKey,
+        project_root: Option<&Path>,
+    ) -> Result<(), Error> {
         let mut wtxn = self.env.write_txn().map_err(Error::DbStartWriteTxn)?;
 
         let key_hash = Self::path_to_hash_bytes(&file_key.path);
         let mut accesses = self.get_accesses(file_key)?.unwrap_or_default();
+        let previous_access = accesses.back().copied();
 
         let now = self.get_now();
         let cutoff_time = now.saturating_sub((MAX_HISTORY_DAYS * SECONDS_PER_DAY) as u64);
@@ -89,12 +274,135 @@ impl FrecencyTracker {
         self.db
             .put(&mut wtxn, &key_hash, &accesses)
             .map_err(Error::DbWrite)?;
+        self.paths_db
+            .put(&mut wtxn, &key_hash, &file_key.path)
+            .map_err(Error::DbWrite)?;
+        if let Some(previous_access) = previous_access {
+            self.recent_db
+                .delete(&mut wtxn, &recent_key(previous_access, &key_hash))
+                .map_err(Error::DbWrite)?;
+        }
+        self.recent_db
+            .put(&mut wtxn, &recent_key(now, &key_hash), &())
+            .map_err(Error::DbWrite)?;
+        if let Some(project_root) = project_root.filter(|_| Path::new(&file_key.path).is_relative())
+        {
+            self.projects_db
+                .put(&mut wtxn, &project_key(project_root, &key_hash), &())
+                .map_err(Error::DbWrite)?;
+        }
+
+        wtxn.commit().map_err(Error::DbCommit)?;
+        self.record_local_write();
+
+        Ok(())
+    }
+
+    /// Deletes the records of the paths tracked in the project at `project_root` that are missing
+    /// from its `known_paths`, returns how many were deleted. Records another project tracked
+    /// as well are kept, and so are records tracked before projects were remembered.
+    pub fn prune_for_workspace(
+        &self,
+        project_root: &Path,
+        known_paths: &[String],
+    ) -> Result<usize, Error> {
+        let known_hashes: HashSet<[u8; 32]> = known_paths
+            .iter()
+            .map(|path| Self::path_to_hash_bytes(path))
+            .collect();
+
+        let prefix = project_prefix(project_root);
+        let mut wtxn = self.env.write_txn().map_err(Error::DbStartWriteTxn)?;
+        let mut stale_hashes = HashSet::new();
+        for entry in self
+            .projects_db
+            .prefix_iter(&wtxn, &prefix)
+            .map_err(Error::DbRead)?
+        {
+            let (key, ()) = entry.map_err(Error::DbRead)?;
+            if let Some(hash) = key_hash_of(key).filter(|hash| !known_hashes.contains(hash)) {
+                stale_hashes.insert(hash);
+            }
+        }
+        for hash in &stale_hashes {
+            self.projects_db
+                .delete(&mut wtxn, &project_key(project_root, hash))
+                .map_err(Error::DbWrite)?;
+        }
+        for entry in self.projects_db.iter(&wtxn).map_err(Error::DbRead)? {
+            let (key, ()) = entry.map_err(Error::DbRead)?;
+            if let Some(hash) = key_hash_of(key) {
+                stale_hashes.remove(&hash);
+            }
+        }
 
+        for hash in &stale_hashes {
+            let accesses = self.db.get(&wtxn, hash).map_err(Error::DbRead)?;
+            if let Some(&last_access) = accesses.as_ref().and_then(VecDeque::back) {
+                self.recent_db
+                    .delete(&mut wtxn, &recent_key(last_access, hash))
+                    .map_err(Error::DbWrite)?;
+            }
+            self.db.delete(&mut wtxn, hash).map_err(Error::DbWrite)?;
+            self.paths_db
+                .delete(&mut wtxn, hash)
+                .map_err(Error::DbWrite)?;
+        }
         wtxn.commit().map_err(Error::DbCommit)?;
+        self.record_local_write();
 
+        Ok(stale_hashes.len())
+    }
+
+    /// Search selections made in the project at `base_path`, oldest first
+    pub fn load_search_history(&self, base_path: &Path) -> Result<Vec<SearchHistoryEntry>, Error> {
+        let rtxn = self.env.read_txn().map_err(Error::DbStartReadTxn)?;
+        let entries = self
+            .history_db
+            .get(&rtxn, &base_path.to_string_lossy())
+            .map_err(Error::DbRead)?;
+        Ok(entries.unwrap_or_default())
+    }
+
+    /// Replaces the stored selections of the project at `base_path`, nothing is kept for it
+    /// when `entries` is empty
+    pub fn save_search_history(
+        &self,
+        base_path: &Path,
+        entries: &[SearchHistoryEntry],
+    ) -> Result<(), Error> {
+        let key = base_path.to_string_lossy();
+        let mut wtxn = self.env.write_txn().map_err(Error::DbStartWriteTxn)?;
+        if entries.is_empty() {
+            self.history_db
+                .delete(&mut wtxn, &key)
+                .map_err(Error::DbWrite)?;
+        } else {
+            self.history_db
+                .put(&mut wtxn, &key, &entries.to_vec())
+                .map_err(Error::DbWrite)?;
+        }
+        wtxn.commit().map_err(Error::DbCommit)?;
+        self.record_local_write();
         Ok(())
     }
 
+    /// Every tracked path sorted alphabetically, capped at `MAX_TRACKED_PATHS` entries.
+    /// Files last accessed before paths were recorded alongside their hash are not listed.
+    pub fn all_paths(&self) -> Result<Vec<String>, Error> {
+        let rtxn = self.env.read_txn().map_err(Error::DbStartReadTxn)?;
+        let mut paths = Vec::new();
+        for entry in self.paths_db.iter(&rtxn).map_err(Error::DbRead)? {
+            let (_, path) = entry.map_err(Error::DbRead)?;
+            paths.push(path.to_string());
+        }
+
+        paths.sort_unstable();
+        paths.dedup();
+        paths.truncate(MAX_TRACKED_PATHS);
+        Ok(paths)
+    }
+
     pub fn get_access_score(&self, file_key: &FileKey) -> i64 {
         let accesses = self
             .get_accesses(file_key)
@@ -153,6 +461,36 @@ impl FrecencyTracker {
     }
 }
 
+fn project_prefix(project_root: &Path) -> Vec<u8> {
+    let mut prefix = project_root.to_string_lossy().into_owned().into_bytes();
+    prefix.push(0);
+    prefix
+}
+
+fn project_key(project_root: &Path, key_hash: &[u8; 32]) -> Vec<u8> {
+    let mut key = project_prefix(project_root);
+    key.extend_from_slice(key_hash);
+    key
+}
+
+fn recent_key(last_access: u64, key_hash: &[u8; 32]) -> [u8; 40] {
+    let mut key = [0; 40];
+    key[..8].copy_from_slice(&last_access.to_be_bytes());
+    key[8..].copy_from_slice(key_hash);
+    key
+}
+
+/// The key hash a `projects_db` or `recent_db` key ends with
+fn key_hash_of(project_key: &[u8]) -> Option<[u8; 32]> {
+    let start = project_key.len().checked_sub(32)?;
+    project_key[start..].try_into().ok()
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,6 +513,225 @@ mod tests {
         normalized_frecency.round() as i64
     }
 
+    #[test]
+    fn test_try_recover_clears_poison() {
+        let lock: RwLock<Option<FrecencyTracker>> = RwLock::new(None);
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = lock.write().unwrap();
+            panic!("poison the lock");
+        });
+        assert!(lock.is_poisoned());
+
+        assert!(FrecencyTracker::try_recover(&lock));
+        assert!(!lock.is_poisoned());
+        assert!(lock.read().is_ok());
+    }
+
+    #[test]
+    fn test_prune_for_workspace() {
+        let db_path = std::env::temp_dir().join(format!("fff_prune_{}", std::process::id()));
+        let tracker = FrecencyTracker::new(&db_path.to_string_lossy(), true).unwrap();
+        let (project, other_project) = (Path::new("/project"), Path::new("/other"));
+        let track = |path: &str, project_root: Option<&Path>| {
+            tracker
+                .track_access(
+                    &FileKey {
+                        path: path.to_string(),
+                    },
+                    project_root,
+                )
+                .unwrap()
+        };
+        for path in ["src/main.rs", "src/old.rs", "src/shared.rs", "README.md"] {
+            track(path, Some(project));
+        }
+        track("src/shared.rs", Some(other_project));
+        track("lib.rs", Some(other_project));
+        // neither part of a project nor relative to one
+        track("src/untracked.rs", None);
+        track("/elsewhere/notes.md", Some(project));
+
+        let known_paths = ["README.md".to_string(), "src/main.rs".to_string()];
+        // src/shared.rs is still tracked by the other project
+        assert_eq!(
+            tracker.prune_for_workspace(project, &known_paths).unwrap(),
+            1
+        );
+        assert_eq!(
+            tracker.all_paths().unwrap(),
+            [
+                "/elsewhere/notes.md",
+                "README.md",
+                "lib.rs",
+                "src/main.rs",
+                "src/shared.rs",
+                "src/untracked.rs"
+            ]
+        );
+        assert_eq!(
+            tracker.prune_for_workspace(project, &known_paths).unwrap(),
+            0
+        );
+
+        let known_paths = ["lib.rs".to_string()];
+        assert_eq!(
+            tracker
+                .prune_for_workspace(other_project, &known_paths)
+                .unwrap(),
+            1
+        );
+        assert!(!tracker
+            .all_paths()
+            .unwrap()
+            .contains(&"src/shared.rs".to_string()));
+
+        drop(tracker);
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
+    #[test]
+    fn test_get_accesses_since() {
+        let db_path = std::env::temp_dir().join(format!("fff_recent_{}", std::process::id()));
+        let tracker = FrecencyTracker::new(&db_path.to_string_lossy(), true).unwrap();
+        let file_key = |path: &str| FileKey {
+            path: path.to_string(),
+        };
+        let hash = |path: &str| FrecencyTracker::path_to_hash_bytes(path);
+
+        let before = tracker.get_now();
+        tracker
+            .track_access(&file_key("src/main.rs"), None)
+            .unwrap();
+        tracker.track_access(&file_key("src/lib.rs"), None).unwrap();
+        // accessed again, only the last access stays in the index
+        tracker
+            .track_access(&file_key("src/main.rs"), None)
+            .unwrap();
+        let rtxn = tracker.env.read_txn().unwrap();
+        assert_eq!(tracker.recent_db.len(&rtxn).unwrap(), 2);
+        drop(rtxn);
+
+        assert_eq!(
+            tracker.get_accesses_since(before).unwrap(),
+            HashSet::from([hash("src/main.rs"), hash("src/lib.rs")])
+        );
+        assert!(tracker
+            .get_accesses_since(tracker.get_now() + 1)
+            .unwrap()
+            .is_empty());
+
+        tracker
+            .prune_for_workspace(Path::new("/project"), &[])
+            .unwrap();
+        tracker
+            .track_access(&file_key("src/old.rs"), Some(Path::new("/project")))
+            .unwrap();
+        tracker
+            .prune_for_workspace(Path::new("/project"), &[])
+            .unwrap();
+        // pruned records leave the index as well
+        assert!(!tracker
+            .get_accesses_since(before)
+            .unwrap()
+            .contains(&hash("src/old.rs")));
+
+        drop(tracker);
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
+    #[test]
+    fn test_search_history_roundtrip() {
+        let db_path = std::env::temp_dir().join(format!("fff_history_{}", std::process::id()));
+        let tracker = FrecencyTracker::new(&db_path.to_string_lossy(), true).unwrap();
+        let project = Path::new("/home/user/project");
+        let entries = vec![SearchHistoryEntry {
+            query: "main".to_string(),
+            selected_path: "src/main.rs".to_string(),
+            timestamp: 1_700_000_000,
+        }];
+
+        assert!(tracker.load_search_history(project).unwrap().is_empty());
+        tracker.save_search_history(project, &entries).unwrap();
+        assert_eq!(tracker.load_search_history(project).unwrap(), entries);
+        assert!(tracker
+            .load_search_history(Path::new("/home/user/other"))
+            .unwrap()
+            .is_empty());
+
+        tracker.save_search_history(project, &[]).unwrap();
+        assert!(tracker.load_search_history(project).unwrap().is_empty());
+        // stored in the tracker's environment, not one of its own
+        assert!(fs::read_dir(&db_path)
+            .unwrap()
+            .all(|entry| !entry.unwrap().path().is_dir()));
+
+        drop(tracker);
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
+    #[test]
+    fn test_external_modification_is_detected() {
+        let db_path = std::env::temp_dir().join(format!("fff_external_{}", std::process::id()));
+        let tracker = FrecencyTracker::new(&db_path.to_string_lossy(), true).unwrap();
+        tracker
+            .track_access(
+                &FileKey {
+                    path: "src/main.rs".to_string(),
+                },
+                None,
+            )
+            .unwrap();
+
+        // local writes are not external modifications
+        std::thread::sleep(EXTERNAL_CHANGE_DEBOUNCE * 2);
+        assert!(!tracker.take_external_modification());
+
+        // a data file newer than the last local write means another process wrote to it
+        fs::File::options()
+            .write(true)
+            .open(db_path.join(DB_DATA_FILE))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while !tracker.take_external_modification() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        drop(tracker);
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
+    #[test]
+    fn test_absolute_and_relative_paths_share_a_record() {
+        let db_path = std::env::temp_dir().join(format!("fff_file_key_{}", std::process::id()));
+        let tracker = FrecencyTracker::new(&db_path.to_string_lossy(), true).unwrap();
+        let base_path = Path::new("/project");
+
+        let absolute = FileKey::normalize("/project/src/main.rs".to_string(), Some(base_path));
+        let relative = FileKey::normalize("src/main.rs".to_string(), Some(base_path));
+        tracker.track_access(&absolute, Some(base_path)).unwrap();
+        tracker.track_access(&relative, Some(base_path)).unwrap();
+
+        assert_eq!(tracker.all_paths().unwrap(), ["src/main.rs"]);
+        assert_eq!(
+            tracker.get_accesses(&relative).unwrap().map(|a| a.len()),
+            Some(2)
+        );
+
+        // paths outside the base or without one are kept as given
+        let outside = FileKey::normalize("/elsewhere/lib.rs".to_string(), Some(base_path));
+        assert_eq!(outside.path, "/elsewhere/lib.rs");
+        assert_eq!(
+            FileKey::normalize("/project/src/main.rs".to_string(), None).path,
+            "/project/src/main.rs"
+        );
+
+        drop(tracker);
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
     #[test]
     fn test_frecency_calculation() {
         let current_time = 1000000000; // Base timestamp
@@ -1,19 +1,37 @@
 use crate::error::Error;
+use crate::export::{export_files, ExportFormat};
 use crate::file_key::FileKey;
-use crate::file_picker::FilePicker;
+use crate::file_picker::{
+    wait_for_picker_scan, FilePicker, BRANCH_CHANGE_BONUS, CONFIG_FILE_PENALTY,
+    DIRECTORY_FRECENCY_WEIGHT, EARLY_EXIT_THRESHOLD, EXCLUDED_EXTENSIONS,
+    EXCLUDE_PLATFORM_ARTIFACTS, FILENAME_SIMILARITY_WEIGHT, FRECENCY_WEIGHT_ACCESS,
+    FRECENCY_WEIGHT_MODIFICATION, HIDDEN_FILE_PENALTY, INCLUDED_EXTENSIONS, INCLUDE_SUBMODULES,
+    SCANNER_THREADS, SEARCH_HISTORY_CAPACITY, SNAPSHOT_HISTORY_ENABLED, WATCHER_DEBOUNCE,
+};
 use crate::frecency::FrecencyTracker;
-use crate::types::{FileItem, SearchResult};
+use crate::memory::{available_memory_mb, LOW_MEMORY_THRESHOLD_MB};
+use crate::path_utils::{SimilarityAlgorithm, MAX_PENALTY_LEVELS, SIMILARITY_ALGORITHM};
+use crate::query::QUERY_PREFIX_ENABLED;
+use crate::search_mode::SearchMode;
+use crate::types::{DirStats, FileItem, SearchHistoryEntry, SearchResult, SlowDirectory};
 use mlua::prelude::*;
-use std::sync::{LazyLock, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::Ordering, LazyLock, RwLock};
 use std::time::Duration;
 
 mod error;
+mod export;
 mod file_key;
 mod file_picker;
 mod frecency;
 mod git;
+mod log_buffer;
+mod memory;
 mod path_utils;
+mod query;
 pub(crate) mod score;
+mod search_mode;
 mod tracing;
 pub(crate) mod types;
 
@@ -21,7 +39,19 @@ static FRECENCY: LazyLock<RwLock<Option<FrecencyTracker>>> = LazyLock::new(|| Rw
 static FILE_PICKER: LazyLock<RwLock<Option<FilePicker>>> = LazyLock::new(|| RwLock::new(None));
 
 pub fn init_db(_: &Lua, (db_path, use_unsafe_no_lock): (String, bool)) -> LuaResult<bool> {
-    let mut frecency = FRECENCY.write().map_err(|_| Error::AcquireFrecencyLock)?;
+    // cheap read lock first so calls on an already initialized db never wait for writers
+    let is_initialized = FRECENCY
+        .read()
+        .map_err(|_| Error::AcquireFrecencyLock("init_db"))?
+        .is_some();
+    if is_initialized {
+        return Ok(false);
+    }
+
+    // another caller may have initialized it between dropping the read lock and getting this one
+    let mut frecency = FRECENCY
+        .write()
+        .map_err(|_| Error::AcquireFrecencyLock("init_db"))?;
     if frecency.is_some() {
         return Ok(false);
     }
@@ -30,24 +60,63 @@ pub fn init_db(_: &Lua, (db_path, use_unsafe_no_lock): (String, bool)) -> LuaRes
 }
 
 pub fn destroy_db(_: &Lua, _: ()) -> LuaResult<bool> {
-    let mut frecency = FRECENCY.write().map_err(|_| Error::AcquireFrecencyLock)?;
+    let mut frecency = FRECENCY
+        .write()
+        .map_err(|_| Error::AcquireFrecencyLock("destroy_db"))?;
     *frecency = None;
     Ok(true)
 }
 
-pub fn init_file_picker(_: &Lua, base_path: String) -> LuaResult<bool> {
-    let mut file_picker = FILE_PICKER.write().map_err(|_| Error::AcquireItemLock)?;
+pub fn init_file_picker(
+    _: &Lua,
+    (base_path, cache_path): (String, Option<String>),
+) -> LuaResult<bool> {
+    let is_initialized = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("init_file_picker"))?
+        .is_some();
+    if is_initialized {
+        return Ok(false);
+    }
+
+    let mut file_picker = FILE_PICKER
+        .write()
+        .map_err(|_| Error::AcquireItemLock("init_file_picker"))?;
     if file_picker.is_some() {
         return Ok(false);
     }
 
-    let picker = FilePicker::new(base_path)?;
+    let picker = FilePicker::with_file_cache(base_path, cache_path.map(PathBuf::from))?;
+    restore_search_history(&picker);
     *file_picker = Some(picker);
     Ok(true)
 }
 
+pub fn reset_file_picker(_: &Lua, _: ()) -> LuaResult<bool> {
+    let mut file_picker = FILE_PICKER
+        .write()
+        .map_err(|_| Error::AcquireItemLock("reset_file_picker"))?;
+    *file_picker = None;
+    Ok(true)
+}
+
+pub fn set_base_path(_: &Lua, new_base_path: String) -> LuaResult<bool> {
+    let mut file_picker = FILE_PICKER
+        .write()
+        .map_err(|_| Error::AcquireItemLock("set_base_path"))?;
+    let picker = file_picker
+        .as_mut()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    picker.set_base_path(new_base_path)?;
+    restore_search_history(picker);
+    Ok(true)
+}
+
 pub fn scan_files(_: &Lua, _: ()) -> LuaResult<()> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("scan_files"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
@@ -58,39 +127,459 @@ pub fn scan_files(_: &Lua, _: ()) -> LuaResult<()> {
 }
 
 pub fn get_cached_files(_: &Lua, _: ()) -> LuaResult<Vec<FileItem>> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_cached_files"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
     Ok(picker.get_cached_files())
 }
 
+pub fn get_file_with_preview(
+    lua: &Lua,
+    (path, max_bytes): (String, Option<usize>),
+) -> LuaResult<LuaTable> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_file_with_preview"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    let file = picker
+        .get_file(&path)
+        .ok_or_else(|| Error::InvalidPath(format!("File not found in index: {}", path)))?;
+
+    let max_bytes = max_bytes.unwrap_or(64 * 1024) as u64; // Default 64KB
+    let content_preview = file.content_preview(max_bytes);
+
+    let LuaValue::Table(table) = file.into_lua(lua)? else {
+        return Err(LuaError::RuntimeError(
+            "FileItem did not convert to a table".to_string(),
+        ));
+    };
+    table.set("content_preview", content_preview)?;
+    Ok(table)
+}
+
 pub fn fuzzy_search_files(
     _: &Lua,
-    (query, max_results, max_threads, current_file): (String, usize, usize, Option<String>),
+    (query, max_results, max_threads, current_file, options): (
+        String,
+        usize,
+        usize,
+        Option<String>,
+        Option<LuaTable>,
+    ),
 ) -> LuaResult<SearchResult> {
+    let max_typos = match options {
+        Some(options) => options.get::<Option<u16>>("max_typos")?,
+        None => None,
+    };
+
+    // search results are still useful without frecency so a failed recovery isn't fatal
+    let _ = recover_frecency_if_poisoned("fuzzy_search_files");
+
     let time = std::time::Instant::now();
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("fuzzy_search_files"))?;
     ::tracing::debug!("Fuzzy search started: {:?}", time.elapsed());
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
 
-    let results = picker.fuzzy_search(&query, max_results, max_threads, current_file.as_ref());
+    let results = picker.fuzzy_search(
+        &query,
+        max_results,
+        max_threads,
+        current_file.as_ref(),
+        max_typos,
+    );
     Ok(results)
 }
 
+pub fn fuzzy_search_files_timed(
+    _: &Lua,
+    (query, max_results, max_threads, current_file, timeout_ms): (
+        String,
+        usize,
+        usize,
+        Option<String>,
+        u64,
+    ),
+) -> LuaResult<SearchResult> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("fuzzy_search_files_timed"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    Ok(picker.fuzzy_search_timed(
+        &query,
+        max_results,
+        max_threads,
+        current_file.as_ref(),
+        Duration::from_millis(timeout_ms),
+    ))
+}
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+
+/// Searches with the mode given in `config.mode`: "fuzzy" (default), "regex", "exact" or
+/// "anchored". `config` also accepts `max_results`, `max_threads`, `current_file` and `max_typos`.
+pub fn search_files(
+    _: &Lua,
+    (query, config): (String, Option<LuaTable>),
+) -> LuaResult<SearchResult> {
+    let (mode, max_results, max_threads, current_file, max_typos) = match config {
+        Some(config) => (
+            config.get::<Option<String>>("mode")?,
+            config.get::<Option<usize>>("max_results")?,
+            config.get::<Option<usize>>("max_threads")?,
+            config.get::<Option<String>>("current_file")?,
+            config.get::<Option<u16>>("max_typos")?,
+        ),
+        None => (None, None, None, None, None),
+    };
+    let mode = match mode {
+        Some(name) => SearchMode::from_name(&name)
+            .ok_or_else(|| LuaError::RuntimeError(format!("Unknown search mode: {}", name)))?,
+        None => SearchMode::default(),
+    };
+
+    let _ = recover_frecency_if_poisoned("search_files");
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("search_files"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    Ok(picker.search(
+        &query,
+        mode,
+        max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+        max_threads.unwrap_or_else(rayon::current_num_threads),
+        current_file.as_ref(),
+        max_typos,
+    ))
+}
+
+/// Writes the results of `query` to `output_path` for other tools, `format` is "paths" (one
+/// relative path per line, default), "json" or "nul" (NUL separated, for `xargs -0`)
+pub fn export_search_results(
+    _: &Lua,
+    (query, output_path, format): (String, String, Option<String>),
+) -> LuaResult<usize> {
+    let format = match format {
+        Some(name) => ExportFormat::from_name(&name)
+            .ok_or_else(|| LuaError::RuntimeError(format!("Unknown export format: {}", name)))?,
+        None => ExportFormat::default(),
+    };
+
+    let _ = recover_frecency_if_poisoned("export_search_results");
+    let results = {
+        let file_picker = FILE_PICKER
+            .read()
+            .map_err(|_| Error::AcquireItemLock("export_search_results"))?;
+        let picker = file_picker
+            .as_ref()
+            .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+        picker.fuzzy_search(&query, usize::MAX, rayon::current_num_threads(), None, None)
+    };
+
+    Ok(export_files(
+        &results.items,
+        Path::new(&output_path),
+        format,
+    )?)
+}
+
+pub fn anchored_search_files(
+    _: &Lua,
+    (query, max_results): (String, usize),
+) -> LuaResult<SearchResult> {
+    let _ = recover_frecency_if_poisoned("anchored_search_files");
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("anchored_search_files"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    Ok(picker.anchored_search(&query, max_results, rayon::current_num_threads()))
+}
+
+pub fn navigate_to_previous_snapshot(_: &Lua, _: ()) -> LuaResult<bool> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("navigate_to_previous_snapshot"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.navigate_to_previous_snapshot())
+}
+
+pub fn navigate_to_current(_: &Lua, _: ()) -> LuaResult<bool> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("navigate_to_current"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    picker.navigate_to_current();
+    Ok(true)
+}
+
+/// Replaces directories of `path` with `…` until it is at most `max_width` characters wide
+pub fn shorten_path(_: &Lua, (path, max_width): (String, usize)) -> LuaResult<String> {
+    Ok(path_utils::shorten_path(&path, max_width))
+}
+
+/// `path` without the leading `prefix` directory, unchanged when it is not inside `prefix`
+pub fn strip_path_prefix(_: &Lua, (path, prefix): (String, String)) -> LuaResult<String> {
+    Ok(path_utils::strip_path_prefix(&path, &prefix).to_string())
+}
+
+pub fn get_file_diff(
+    _: &Lua,
+    (file_path, context_lines): (String, Option<usize>),
+) -> LuaResult<Option<String>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_file_diff"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    Ok(picker.get_file_diff(&file_path, context_lines.unwrap_or(3)))
+}
+
+/// The path the picker indexes, `nil` before it is initialized
+pub fn get_file_picker_base_path(_: &Lua, _: ()) -> LuaResult<Option<String>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_file_picker_base_path"))?;
+    Ok(file_picker
+        .as_ref()
+        .map(|picker| picker.base_path().to_string_lossy().into_owned()))
+}
+
+/// Root of the git repository the picker indexes, `nil` outside of a repository or before the
+/// picker is initialized
+pub fn get_file_picker_git_workdir(_: &Lua, _: ()) -> LuaResult<Option<String>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_file_picker_git_workdir"))?;
+    Ok(file_picker
+        .as_ref()
+        .and_then(FilePicker::git_workdir)
+        .map(|path| path.to_string_lossy().into_owned()))
+}
+
+/// Git stashes as of the last scan, 0 outside of a repository
+pub fn get_stash_count(_: &Lua, _: ()) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_stash_count"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    Ok(picker.stash_count())
+}
+
+pub fn get_stash_list(lua: &Lua, _: ()) -> LuaResult<Vec<LuaTable>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_stash_list"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    picker
+        .get_stash_list()
+        .into_iter()
+        .map(|stash| {
+            let table = lua.create_table()?;
+            table.set("index", stash.index)?;
+            table.set("message", stash.message)?;
+            table.set("oid", stash.oid)?;
+            Ok(table)
+        })
+        .collect()
+}
+
+/// A panic while the frecency lock was held poisons it for good, recover it once before giving up
+fn recover_frecency_if_poisoned(operation: &'static str) -> Result<(), Error> {
+    if FRECENCY.is_poisoned() && !FrecencyTracker::try_recover(&FRECENCY) {
+        return Err(Error::AcquireFrecencyLock(operation));
+    }
+    Ok(())
+}
+
 pub fn access_file(_: &Lua, file_path: String) -> LuaResult<bool> {
-    let frecency = FRECENCY.read().map_err(|_| Error::AcquireFrecencyLock)?;
+    let base_path = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("access_file"))?
+        .as_ref()
+        .map(|picker| picker.base_path().to_path_buf());
+
+    recover_frecency_if_poisoned("access_file")?;
+    let frecency = FRECENCY
+        .read()
+        .map_err(|_| Error::AcquireFrecencyLock("access_file"))?;
     if let Some(ref tracker) = *frecency {
-        let file_key = FileKey { path: file_path };
-        tracker.track_access(&file_key)?;
+        let file_key = FileKey::normalize(file_path, base_path.as_deref());
+        tracker.track_access(&file_key, base_path.as_deref())?;
     }
     Ok(true)
 }
 
+/// Whether another process, e.g. a second Neovim instance, wrote to the frecency database
+/// since the last call
+pub fn is_db_externally_modified(_: &Lua, _: ()) -> LuaResult<bool> {
+    recover_frecency_if_poisoned("is_db_externally_modified")?;
+    let frecency = FRECENCY
+        .read()
+        .map_err(|_| Error::AcquireFrecencyLock("is_db_externally_modified"))?;
+    Ok(frecency
+        .as_ref()
+        .is_some_and(FrecencyTracker::take_external_modification))
+}
+
+/// Loads the persisted search history of the picker's project, a failure only loses the history
+fn restore_search_history(picker: &FilePicker) {
+    let Ok(frecency) = FRECENCY.read() else {
+        return;
+    };
+    if let Some(ref tracker) = *frecency {
+        match tracker.load_search_history(picker.base_path()) {
+            Ok(entries) => picker.set_search_history(entries),
+            Err(e) => ::tracing::warn!("Failed to load search history: {:?}", e),
+        }
+    }
+}
+
+fn persist_search_history(
+    operation: &'static str,
+    base_path: &Path,
+    entries: &[SearchHistoryEntry],
+) -> Result<(), Error> {
+    recover_frecency_if_poisoned(operation)?;
+    let frecency = FRECENCY
+        .read()
+        .map_err(|_| Error::AcquireFrecencyLock(operation))?;
+    if let Some(ref tracker) = *frecency {
+        tracker.save_search_history(base_path, entries)?;
+    }
+    Ok(())
+}
+
+pub fn record_search_selection(
+    _: &Lua,
+    (query, selected_path): (String, String),
+) -> LuaResult<bool> {
+    let (base_path, entries) = {
+        let file_picker = FILE_PICKER
+            .read()
+            .map_err(|_| Error::AcquireItemLock("record_search_selection"))?;
+        let picker = file_picker
+            .as_ref()
+            .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+        let entries = picker.record_search_selection(query, selected_path);
+        (picker.base_path().to_path_buf(), entries)
+    };
+
+    persist_search_history("record_search_selection", &base_path, &entries)?;
+    Ok(true)
+}
+
+/// Most recent selections first, all of them when `limit` is not given
+pub fn get_search_history(lua: &Lua, limit: Option<usize>) -> LuaResult<Vec<LuaTable>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_search_history"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    picker
+        .search_history(limit.unwrap_or(SEARCH_HISTORY_CAPACITY))
+        .into_iter()
+        .map(|entry| {
+            let table = lua.create_table()?;
+            table.set("query", entry.query)?;
+            table.set("selected_path", entry.selected_path)?;
+            table.set("timestamp", entry.timestamp)?;
+            Ok(table)
+        })
+        .collect()
+}
+
+pub fn clear_search_history(_: &Lua, _: ()) -> LuaResult<bool> {
+    let base_path = {
+        let file_picker = FILE_PICKER
+            .read()
+            .map_err(|_| Error::AcquireItemLock("clear_search_history"))?;
+        let picker = file_picker
+            .as_ref()
+            .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+        picker.clear_search_history();
+        picker.base_path().to_path_buf()
+    };
+
+    persist_search_history("clear_search_history", &base_path, &[])?;
+    Ok(true)
+}
+
+pub fn get_all_tracked_paths(_: &Lua, _: ()) -> LuaResult<Vec<String>> {
+    recover_frecency_if_poisoned("get_all_tracked_paths")?;
+    let frecency = FRECENCY
+        .read()
+        .map_err(|_| Error::AcquireFrecencyLock("get_all_tracked_paths"))?;
+    match *frecency {
+        Some(ref tracker) => Ok(tracker.all_paths()?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn prune_stale_frecency(_: &Lua, _: ()) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("prune_stale_frecency"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    if picker.is_scan_active() {
+        // a partial file list would prune records of files that simply weren't scanned yet
+        return Err(LuaError::RuntimeError(
+            "Can not prune frecency while the file scan is running".to_string(),
+        ));
+    }
+    let known_paths: Vec<String> = picker
+        .get_cached_files()
+        .into_iter()
+        .map(|file| file.relative_path)
+        .collect();
+
+    recover_frecency_if_poisoned("prune_stale_frecency")?;
+    let frecency = FRECENCY
+        .read()
+        .map_err(|_| Error::AcquireFrecencyLock("prune_stale_frecency"))?;
+    match *frecency {
+        Some(ref tracker) => Ok(tracker.prune_for_workspace(picker.base_path(), &known_paths)?),
+        None => Ok(0),
+    }
+}
+
 pub fn get_scan_progress(lua: &Lua, _: ()) -> LuaResult<LuaValue> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_scan_progress"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
@@ -99,20 +588,125 @@ pub fn get_scan_progress(lua: &Lua, _: ()) -> LuaResult<LuaValue> {
     let table = lua.create_table()?;
     table.set("total_files", progress.total_files)?;
     table.set("scanned_files", progress.scanned_files)?;
+    table.set("skipped_file_count", progress.skipped_files)?;
     table.set("is_scanning", progress.is_scanning)?;
     Ok(LuaValue::Table(table))
 }
 
 pub fn is_scanning(_: &Lua, _: ()) -> LuaResult<bool> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("is_scanning"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
-    Ok(picker.is_scan_active())
+    // same source as `get_scan_progress` so the two never disagree
+    Ok(picker.get_scan_progress().is_scanning)
+}
+
+pub fn save_file_cache(_: &Lua, path: String) -> LuaResult<bool> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("save_file_cache"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    picker.save_file_cache(Path::new(&path))?;
+    Ok(true)
+}
+
+pub fn load_file_cache(_: &Lua, path: String) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("load_file_cache"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.load_file_cache(Path::new(&path))?)
+}
+
+pub fn get_directory_stats(_: &Lua, _: ()) -> LuaResult<HashMap<String, DirStats>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_directory_stats"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.get_directory_stats())
+}
+
+pub fn get_slow_directories(_: &Lua, _: ()) -> LuaResult<Vec<SlowDirectory>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_slow_directories"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.get_slow_directories())
+}
+
+/// Files the last scan left out of the index after visiting them, see
+/// `FilePicker::get_skipped_file_count`. Files excluded by .gitignore and exclude patterns are
+/// not part of the count, the scan never visits them.
+pub fn get_ignored_file_count(_: &Lua, _: ()) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_ignored_file_count"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.get_skipped_file_count())
+}
+
+pub fn compact_file_cache(
+    _: &Lua,
+    (min_frecency, max_file_count): (i64, Option<usize>),
+) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("compact_file_cache"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.compact_files(min_frecency, max_file_count)?)
+}
+
+pub fn get_memory_usage_bytes(_: &Lua, _: ()) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_memory_usage_bytes"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.get_memory_usage_bytes())
+}
+
+pub fn get_detected_language(_: &Lua, _: ()) -> LuaResult<Option<String>> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_detected_language"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.get_detected_language())
+}
+
+pub fn set_preferred_extensions(_: &Lua, extensions: Vec<String>) -> LuaResult<bool> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("set_preferred_extensions"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+
+    picker.set_preferred_extensions(normalize_extensions(extensions))?;
+    Ok(true)
 }
 
 pub fn refresh_git_status(_: &Lua, _: ()) -> LuaResult<Vec<FileItem>> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("refresh_git_status"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
@@ -120,8 +714,30 @@ pub fn refresh_git_status(_: &Lua, _: ()) -> LuaResult<Vec<FileItem>> {
     Ok(picker.refresh_git_status())
 }
 
+pub fn has_git_changes(_: &Lua, _: ()) -> LuaResult<bool> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("has_git_changes"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.has_git_changes())
+}
+
+pub fn git_changed_file_count(_: &Lua, _: ()) -> LuaResult<usize> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("git_changed_file_count"))?;
+    let picker = file_picker
+        .as_ref()
+        .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    Ok(picker.git_changed_file_count())
+}
+
 pub fn stop_background_monitor(_: &Lua, _: ()) -> LuaResult<bool> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("stop_background_monitor"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
@@ -134,19 +750,312 @@ pub fn cancel_scan(_: &Lua, _: ()) -> LuaResult<bool> {
 }
 
 pub fn wait_for_initial_scan(_: &Lua, timeout_ms: Option<u64>) -> LuaResult<bool> {
-    let file_picker = FILE_PICKER.read().map_err(|_| Error::AcquireItemLock)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000)); // Default 5s timeout
+    Ok(wait_for_picker_scan(&FILE_PICKER, timeout)?)
+}
+
+pub fn set_exclude_extensions(_: &Lua, extensions: Vec<String>) -> LuaResult<bool> {
+    let mut excluded = EXCLUDED_EXTENSIONS
+        .write()
+        .map_err(|_| Error::PoisonedLock {
+            operation: "set_exclude_extensions",
+        })?;
+    *excluded = normalize_extensions(extensions);
+    Ok(true)
+}
+
+pub fn set_include_extensions_only(_: &Lua, extensions: Vec<String>) -> LuaResult<bool> {
+    let mut included = INCLUDED_EXTENSIONS
+        .write()
+        .map_err(|_| Error::PoisonedLock {
+            operation: "set_include_extensions_only",
+        })?;
+    *included = normalize_extensions(extensions);
+    Ok(true)
+}
+
+fn normalize_extensions(extensions: Vec<String>) -> HashSet<String> {
+    extensions
+        .into_iter()
+        .map(|ext| ext.trim_start_matches('.').to_lowercase())
+        .collect()
+}
+
+pub fn set_low_memory_threshold_mb(_: &Lua, mb: u64) -> LuaResult<bool> {
+    LOW_MEMORY_THRESHOLD_MB.store(mb, Ordering::Relaxed);
+    Ok(true)
+}
+
+pub fn get_memory_pressure(lua: &Lua, _: ()) -> LuaResult<LuaValue> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_memory_pressure"))?;
+    let is_paused = file_picker
+        .as_ref()
+        .is_some_and(FilePicker::is_watching_paused);
+
+    let table = lua.create_table()?;
+    table.set("available_mb", available_memory_mb())?;
+    table.set(
+        "threshold_mb",
+        LOW_MEMORY_THRESHOLD_MB.load(Ordering::Relaxed),
+    )?;
+    table.set("is_paused", is_paused)?;
+    Ok(LuaValue::Table(table))
+}
+
+pub fn get_watcher_status(lua: &Lua, _: ()) -> LuaResult<LuaValue> {
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_watcher_status"))?;
     let picker = file_picker
         .as_ref()
         .ok_or_else(|| Error::InvalidPath("File picker not initialized".to_string()))?;
+    let status = picker.get_watcher_status();
 
-    let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000)); // Default 5s timeout
-    let start_time = std::time::Instant::now();
+    let table = lua.create_table()?;
+    table.set("is_running", status.is_running)?;
+    table.set("is_paused", status.is_paused)?;
+    table.set("data_integrity_warning", status.data_integrity_warning)?;
+    table.set("restart_count", status.restart_count)?;
+    Ok(LuaValue::Table(table))
+}
+
+/// Snapshot of the configuration and picker state to attach to bug reports, the picker fields
+/// are `nil` until it is initialized
+pub fn get_debug_info(lua: &Lua, _: ()) -> LuaResult<LuaValue> {
+    let table = lua.create_table()?;
+    table.set("api_version", API_VERSION)?;
+    table.set(
+        "is_db_initialized",
+        FRECENCY
+            .read()
+            .map_err(|_| Error::AcquireFrecencyLock("get_debug_info"))?
+            .is_some(),
+    )?;
+    table.set("rayon_threads", rayon::current_num_threads())?;
+    table.set("scanner_threads", SCANNER_THREADS.load(Ordering::Relaxed))?;
+    table.set("debounce_ms", WATCHER_DEBOUNCE.as_millis() as u64)?;
+    table.set(
+        "include_submodules",
+        INCLUDE_SUBMODULES.load(Ordering::Relaxed),
+    )?;
+    table.set(
+        "snapshot_history_enabled",
+        SNAPSHOT_HISTORY_ENABLED.load(Ordering::Relaxed),
+    )?;
+    table.set(
+        "exclude_platform_artifacts",
+        EXCLUDE_PLATFORM_ARTIFACTS.load(Ordering::Relaxed),
+    )?;
+    table.set(
+        "query_prefix_enabled",
+        QUERY_PREFIX_ENABLED.load(Ordering::Relaxed),
+    )?;
+    table.set(
+        "low_memory_threshold_mb",
+        LOW_MEMORY_THRESHOLD_MB.load(Ordering::Relaxed),
+    )?;
+    table.set(
+        "exclude_extensions",
+        sorted_extensions(&EXCLUDED_EXTENSIONS)?,
+    )?;
+    table.set(
+        "include_extensions_only",
+        sorted_extensions(&INCLUDED_EXTENSIONS)?,
+    )?;
 
-    while picker.is_scan_active() && start_time.elapsed() < timeout {
-        std::thread::sleep(Duration::from_millis(50));
+    let scoring_config = lua.create_table()?;
+    scoring_config.set(
+        "branch_change_bonus",
+        BRANCH_CHANGE_BONUS.load(Ordering::Relaxed),
+    )?;
+    scoring_config.set(
+        "config_file_penalty",
+        CONFIG_FILE_PENALTY.load(Ordering::Relaxed),
+    )?;
+    scoring_config.set(
+        "hidden_file_penalty",
+        HIDDEN_FILE_PENALTY.load(Ordering::Relaxed),
+    )?;
+    scoring_config.set(
+        "directory_frecency_weight",
+        f64::from_bits(DIRECTORY_FRECENCY_WEIGHT.load(Ordering::Relaxed)),
+    )?;
+    scoring_config.set(
+        "frecency_weight_access",
+        FRECENCY_WEIGHT_ACCESS.load(Ordering::Relaxed),
+    )?;
+    scoring_config.set(
+        "frecency_weight_modification",
+        FRECENCY_WEIGHT_MODIFICATION.load(Ordering::Relaxed),
+    )?;
+    scoring_config.set(
+        "max_penalty_multiplier",
+        MAX_PENALTY_LEVELS.load(Ordering::Relaxed),
+    )?;
+    scoring_config.set(
+        "early_exit_threshold",
+        Some(EARLY_EXIT_THRESHOLD.load(Ordering::Relaxed)).filter(|&t| t != usize::MAX),
+    )?;
+    scoring_config.set(
+        "similarity_algorithm",
+        SIMILARITY_ALGORITHM
+            .read()
+            .map_err(|_| Error::PoisonedLock {
+                operation: "get_debug_info",
+            })?
+            .name(),
+    )?;
+    scoring_config.set(
+        "filename_similarity_weight",
+        f64::from_bits(FILENAME_SIMILARITY_WEIGHT.load(Ordering::Relaxed)),
+    )?;
+    table.set("scoring_config", scoring_config)?;
+
+    let file_picker = FILE_PICKER
+        .read()
+        .map_err(|_| Error::AcquireItemLock("get_debug_info"))?;
+    if let Some(info) = file_picker.as_ref().map(FilePicker::get_debug_info) {
+        table.set("base_path", info.base_path.to_string_lossy())?;
+        table.set(
+            "git_workdir",
+            info.git_workdir
+                .map(|path| path.to_string_lossy().into_owned()),
+        )?;
+        table.set("file_count", info.file_count)?;
+        table.set("skipped_file_count", info.skipped_file_count)?;
+        table.set("scan_generation", info.scan_generation)?;
+        table.set("is_scanning", info.is_scanning)?;
+        table.set("is_watching_paused", info.is_watching_paused)?;
+        table.set("viewed_generation", info.viewed_generation)?;
+        table.set(
+            "last_scan_timing_ms",
+            info.last_scan_duration
+                .map(|duration| duration.as_secs_f64() * 1000.0),
+        )?;
+    }
+
+    Ok(LuaValue::Table(table))
+}
+
+fn sorted_extensions(extensions: &RwLock<HashSet<String>>) -> Result<Vec<String>, Error> {
+    let extensions = extensions.read().map_err(|_| Error::PoisonedLock {
+        operation: "get_debug_info",
+    })?;
+    let mut extensions: Vec<String> = extensions.iter().cloned().collect();
+    extensions.sort_unstable();
+    Ok(extensions)
+}
+
+pub fn set_filename_similarity_algorithm(_: &Lua, name: String) -> LuaResult<bool> {
+    let algorithm = SimilarityAlgorithm::from_name(&name)
+        .ok_or_else(|| LuaError::RuntimeError(format!("Unknown similarity algorithm: {}", name)))?;
+
+    let mut current = SIMILARITY_ALGORITHM
+        .write()
+        .map_err(|_| Error::PoisonedLock {
+            operation: "set_filename_similarity_algorithm",
+        })?;
+    *current = algorithm;
+    Ok(true)
+}
+
+/// Multiplier of the up to 20 point bonus for files named like the current file, e.g.
+/// `user_test.rs` while editing `user.rs`. 0 by default, which disables the bonus.
+pub fn set_filename_similarity_weight(_: &Lua, weight: f64) -> LuaResult<bool> {
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(LuaError::RuntimeError(format!(
+            "Filename similarity weight must be a non-negative number, got {}",
+            weight
+        )));
     }
 
-    Ok(!picker.is_scan_active())
+    FILENAME_SIMILARITY_WEIGHT.store(weight.to_bits(), Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Takes effect on the next scan since branch changes are only computed while the bonus is set
+pub fn set_branch_change_bonus(_: &Lua, bonus: i32) -> LuaResult<bool> {
+    BRANCH_CHANGE_BONUS.store(bonus, Ordering::Relaxed);
+    Ok(true)
+}
+
+pub fn set_config_file_penalty(_: &Lua, penalty: i32) -> LuaResult<bool> {
+    CONFIG_FILE_PENALTY.store(penalty, Ordering::Relaxed);
+    Ok(true)
+}
+
+pub fn set_hidden_file_penalty(_: &Lua, penalty: i32) -> LuaResult<bool> {
+    HIDDEN_FILE_PENALTY.store(penalty, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Multipliers of the access and modification frecency when ranking files without a query,
+/// 1 and 4 by default
+pub fn set_frecency_weights(
+    _: &Lua,
+    (access_weight, modification_weight): (i32, i32),
+) -> LuaResult<bool> {
+    FRECENCY_WEIGHT_ACCESS.store(access_weight, Ordering::Relaxed);
+    FRECENCY_WEIGHT_MODIFICATION.store(modification_weight, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Number of directory levels after which the distance penalty stops growing, 10 by default
+pub fn set_max_penalty_multiplier(_: &Lua, levels: i32) -> LuaResult<bool> {
+    MAX_PENALTY_LEVELS.store(levels.max(0), Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Whether files removed from the index are kept for `navigate_to_previous_snapshot`, off by
+/// default. Only files removed from then on are kept.
+pub fn set_snapshot_history_enabled(_: &Lua, enabled: bool) -> LuaResult<bool> {
+    SNAPSHOT_HISTORY_ENABLED.store(enabled, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Takes effect on the next scan, submodules are excluded by default
+pub fn set_include_submodules(_: &Lua, include: bool) -> LuaResult<bool> {
+    INCLUDE_SUBMODULES.store(include, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Whether macOS `._` resource forks and NTFS alternate data streams are left out of the
+/// index, `true` by default. Takes effect on the next scan.
+pub fn set_exclude_platform_artifacts(_: &Lua, exclude: bool) -> LuaResult<bool> {
+    EXCLUDE_PLATFORM_ARTIFACTS.store(exclude, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Takes effect on the next scan, 0 uses one walker thread per CPU
+pub fn set_scanner_threads(_: &Lua, threads: usize) -> LuaResult<bool> {
+    SCANNER_THREADS.store(threads, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Whether `@ext:`, `@dir:` and `@git:` query tokens filter the searched files
+pub fn set_query_prefix_enabled(_: &Lua, enabled: bool) -> LuaResult<bool> {
+    QUERY_PREFIX_ENABLED.store(enabled, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// `nil` disables early termination and always matches every indexed file
+pub fn set_early_exit_threshold(_: &Lua, threshold: Option<usize>) -> LuaResult<bool> {
+    EARLY_EXIT_THRESHOLD.store(threshold.unwrap_or(usize::MAX), Ordering::Relaxed);
+    Ok(true)
+}
+
+pub fn set_directory_frecency_weight(_: &Lua, weight: f64) -> LuaResult<bool> {
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(LuaError::RuntimeError(format!(
+            "Directory frecency weight must be a non-negative number, got {}",
+            weight
+        )));
+    }
+
+    DIRECTORY_FRECENCY_WEIGHT.store(weight.to_bits(), Ordering::Relaxed);
+    Ok(true)
 }
 
 pub fn init_tracing(
@@ -158,29 +1067,250 @@ pub fn init_tracing(
         .map_err(|e| LuaError::RuntimeError(format!("Failed to initialize tracing: {}", e)))
 }
 
+pub fn get_recent_logs(
+    _: &Lua,
+    (count, min_level): (usize, Option<String>),
+) -> LuaResult<Vec<String>> {
+    let min_level = min_level
+        .map(|level| {
+            level
+                .parse::<::tracing::Level>()
+                .map_err(|_| LuaError::RuntimeError(format!("Invalid log level '{}'", level)))
+        })
+        .transpose()?;
+
+    Ok(crate::log_buffer::recent_logs(count, min_level))
+}
+
+/// Bumped on every breaking change of the exported Lua API
+const API_VERSION: u32 = 1;
+
+/// Optional capabilities Lua code can check for before relying on them
+const SUPPORTED_FEATURES: [&str; 15] = [
+    "anchored_search",
+    "search_modes",
+    "query_filters",
+    "timed_search",
+    "file_cache",
+    "file_preview",
+    "recent_logs",
+    "directory_stats",
+    "memory_pressure",
+    "branch_change_bonus",
+    "config_file_penalty",
+    "hidden_file_penalty",
+    "debug_info",
+    "frecency_weights",
+    "max_penalty_multiplier",
+];
+
+pub fn api_version(_: &Lua, _: ()) -> LuaResult<u32> {
+    Ok(API_VERSION)
+}
+
 fn create_exports(lua: &Lua) -> LuaResult<LuaTable> {
     let exports = lua.create_table()?;
+    exports.set("version", API_VERSION)?;
+    exports.set("supported_features", SUPPORTED_FEATURES)?;
+    exports.set("api_version", lua.create_function(api_version)?)?;
     exports.set("init_db", lua.create_function(init_db)?)?;
     exports.set("destroy_db", lua.create_function(destroy_db)?)?;
     exports.set("init_file_picker", lua.create_function(init_file_picker)?)?;
+    exports.set("reset_file_picker", lua.create_function(reset_file_picker)?)?;
+    exports.set("set_base_path", lua.create_function(set_base_path)?)?;
     exports.set("scan_files", lua.create_function(scan_files)?)?;
     exports.set("get_cached_files", lua.create_function(get_cached_files)?)?;
     exports.set(
         "fuzzy_search_files",
         lua.create_function(fuzzy_search_files)?,
     )?;
+    exports.set(
+        "fuzzy_search_files_timed",
+        lua.create_function(fuzzy_search_files_timed)?,
+    )?;
+    exports.set("search_files", lua.create_function(search_files)?)?;
+    exports.set(
+        "export_search_results",
+        lua.create_function(export_search_results)?,
+    )?;
+    exports.set(
+        "navigate_to_previous_snapshot",
+        lua.create_function(navigate_to_previous_snapshot)?,
+    )?;
+    exports.set(
+        "navigate_to_current",
+        lua.create_function(navigate_to_current)?,
+    )?;
+    exports.set(
+        "anchored_search_files",
+        lua.create_function(anchored_search_files)?,
+    )?;
     exports.set("access_file", lua.create_function(access_file)?)?;
+    exports.set(
+        "is_db_externally_modified",
+        lua.create_function(is_db_externally_modified)?,
+    )?;
+    exports.set(
+        "get_all_tracked_paths",
+        lua.create_function(get_all_tracked_paths)?,
+    )?;
+    exports.set(
+        "record_search_selection",
+        lua.create_function(record_search_selection)?,
+    )?;
+    exports.set(
+        "get_search_history",
+        lua.create_function(get_search_history)?,
+    )?;
+    exports.set(
+        "clear_search_history",
+        lua.create_function(clear_search_history)?,
+    )?;
+    exports.set(
+        "prune_stale_frecency",
+        lua.create_function(prune_stale_frecency)?,
+    )?;
+    exports.set("get_file_diff", lua.create_function(get_file_diff)?)?;
+    exports.set(
+        "get_file_picker_base_path",
+        lua.create_function(get_file_picker_base_path)?,
+    )?;
+    exports.set(
+        "get_file_picker_git_workdir",
+        lua.create_function(get_file_picker_git_workdir)?,
+    )?;
+    exports.set("get_stash_count", lua.create_function(get_stash_count)?)?;
+    exports.set("get_stash_list", lua.create_function(get_stash_list)?)?;
+    exports.set("shorten_path", lua.create_function(shorten_path)?)?;
+    exports.set("strip_path_prefix", lua.create_function(strip_path_prefix)?)?;
+    exports.set(
+        "get_file_with_preview",
+        lua.create_function(get_file_with_preview)?,
+    )?;
     exports.set("cancel_scan", lua.create_function(cancel_scan)?)?;
     exports.set("get_scan_progress", lua.create_function(get_scan_progress)?)?;
+    exports.set("save_file_cache", lua.create_function(save_file_cache)?)?;
+    exports.set("load_file_cache", lua.create_function(load_file_cache)?)?;
+    exports.set(
+        "get_ignored_file_count",
+        lua.create_function(get_ignored_file_count)?,
+    )?;
+    exports.set(
+        "compact_file_cache",
+        lua.create_function(compact_file_cache)?,
+    )?;
+    exports.set(
+        "get_memory_usage_bytes",
+        lua.create_function(get_memory_usage_bytes)?,
+    )?;
+    exports.set(
+        "get_detected_language",
+        lua.create_function(get_detected_language)?,
+    )?;
+    exports.set(
+        "set_preferred_extensions",
+        lua.create_function(set_preferred_extensions)?,
+    )?;
+    exports.set(
+        "get_directory_stats",
+        lua.create_function(get_directory_stats)?,
+    )?;
+    exports.set(
+        "get_slow_directories",
+        lua.create_function(get_slow_directories)?,
+    )?;
     exports.set(
         "refresh_git_status",
         lua.create_function(refresh_git_status)?,
     )?;
+    exports.set("has_git_changes", lua.create_function(has_git_changes)?)?;
+    exports.set(
+        "git_changed_file_count",
+        lua.create_function(git_changed_file_count)?,
+    )?;
     exports.set(
         "stop_background_monitor",
         lua.create_function(stop_background_monitor)?,
     )?;
+    exports.set(
+        "set_exclude_extensions",
+        lua.create_function(set_exclude_extensions)?,
+    )?;
+    exports.set(
+        "set_include_extensions_only",
+        lua.create_function(set_include_extensions_only)?,
+    )?;
+    exports.set(
+        "set_low_memory_threshold_mb",
+        lua.create_function(set_low_memory_threshold_mb)?,
+    )?;
+    exports.set(
+        "get_memory_pressure",
+        lua.create_function(get_memory_pressure)?,
+    )?;
+    exports.set(
+        "get_watcher_status",
+        lua.create_function(get_watcher_status)?,
+    )?;
+    exports.set("get_debug_info", lua.create_function(get_debug_info)?)?;
+    exports.set(
+        "set_filename_similarity_algorithm",
+        lua.create_function(set_filename_similarity_algorithm)?,
+    )?;
+    exports.set(
+        "set_filename_similarity_weight",
+        lua.create_function(set_filename_similarity_weight)?,
+    )?;
+    exports.set(
+        "set_branch_change_bonus",
+        lua.create_function(set_branch_change_bonus)?,
+    )?;
+    exports.set(
+        "set_config_file_penalty",
+        lua.create_function(set_config_file_penalty)?,
+    )?;
+    exports.set(
+        "set_hidden_file_penalty",
+        lua.create_function(set_hidden_file_penalty)?,
+    )?;
+    exports.set(
+        "set_frecency_weights",
+        lua.create_function(set_frecency_weights)?,
+    )?;
+    exports.set(
+        "set_max_penalty_multiplier",
+        lua.create_function(set_max_penalty_multiplier)?,
+    )?;
+    exports.set(
+        "set_snapshot_history_enabled",
+        lua.create_function(set_snapshot_history_enabled)?,
+    )?;
+    exports.set(
+        "set_include_submodules",
+        lua.create_function(set_include_submodules)?,
+    )?;
+    exports.set(
+        "set_exclude_platform_artifacts",
+        lua.create_function(set_exclude_platform_artifacts)?,
+    )?;
+    exports.set(
+        "set_scanner_threads",
+        lua.create_function(set_scanner_threads)?,
+    )?;
+    exports.set(
+        "set_query_prefix_enabled",
+        lua.create_function(set_query_prefix_enabled)?,
+    )?;
+    exports.set(
+        "set_early_exit_threshold",
+        lua.create_function(set_early_exit_threshold)?,
+    )?;
+    exports.set(
+        "set_directory_frecency_weight",
+        lua.create_function(set_directory_frecency_weight)?,
+    )?;
     exports.set("init_tracing", lua.create_function(init_tracing)?)?;
+    exports.set("get_recent_logs", lua.create_function(get_recent_logs)?)?;
     exports.set(
         "wait_for_initial_scan",
         lua.create_function(wait_for_initial_scan)?,
@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+const LOG_BUFFER_CAPACITY: usize = 500;
+
+static LOG_BUFFER: LazyLock<Mutex<VecDeque<(Level, String)>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(LOG_BUFFER_CAPACITY)));
+
+/// Tracing layer keeping the most recent log entries in memory for in-editor inspection
+pub struct LogBufferLayer;
+
+impl<S: Subscriber> Layer<S> for LogBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = format!("{} {}:", metadata.level(), metadata.target());
+        event.record(&mut LineVisitor(&mut line));
+
+        let Ok(mut buffer) = LOG_BUFFER.lock() else {
+            return;
+        };
+        if buffer.len() == LOG_BUFFER_CAPACITY {
+            buffer.pop_front();
+        }
+        buffer.push_back((*metadata.level(), line));
+    }
+}
+
+struct LineVisitor<'a>(&'a mut String);
+
+impl Visit for LineVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {}", value);
+        } else {
+            let _ = write!(self.0, " {}={}", field.name(), value);
+        }
+    }
+}
+
+/// Returns up to `count` of the most recent log entries, oldest first, keeping only entries
+/// at least as severe as `min_level` when given
+pub fn recent_logs(count: usize, min_level: Option<Level>) -> Vec<String> {
+    let Ok(buffer) = LOG_BUFFER.lock() else {
+        return Vec::new();
+    };
+
+    let mut logs: Vec<String> = buffer
+        .iter()
+        .rev()
+        .filter(|(level, _)| min_level.is_none_or(|min_level| *level <= min_level))
+        .take(count)
+        .map(|(_, line)| line.clone())
+        .collect();
+    logs.reverse();
+    logs
+}
@@ -0,0 +1,109 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const MEMORY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Free memory threshold in megabytes below which file watching is paused
+pub static LOW_MEMORY_THRESHOLD_MB: AtomicU64 = AtomicU64::new(256);
+
+/// Available system memory in megabytes, `None` when it can not be determined on this platform
+pub fn available_memory_mb() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix("MemAvailable:"))
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map(|kb| kb / 1024)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// The paused state to switch to, `None` to keep `is_paused`. Watching pauses below
+/// `threshold_mb` and resumes above twice that, so it does not flip back and forth around the
+/// threshold.
+fn paused_state_change(is_paused: bool, available_mb: u64, threshold_mb: u64) -> Option<bool> {
+    if !is_paused && available_mb < threshold_mb {
+        Some(true)
+    } else if is_paused && available_mb > threshold_mb.saturating_mul(2) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Periodically checks available memory and toggles `paused` when it drops below the threshold,
+/// watching resumes once memory recovers above twice the threshold
+pub fn spawn_memory_monitor(paused: Arc<AtomicBool>, shutdown: Arc<AtomicBool>) {
+    if available_memory_mb().is_none() {
+        info!("Available memory can not be determined, low memory monitor disabled");
+        return;
+    }
+
+    thread::spawn(move || {
+        while !shutdown.load(Ordering::Relaxed) {
+            if let Some(available_mb) = available_memory_mb() {
+                let threshold_mb = LOW_MEMORY_THRESHOLD_MB.load(Ordering::Relaxed);
+                let is_paused = paused.load(Ordering::Relaxed);
+
+                if let Some(pause) = paused_state_change(is_paused, available_mb, threshold_mb) {
+                    if pause {
+                        warn!(
+                            "Low memory: {}MB available (threshold {}MB), pausing file watching",
+                            available_mb, threshold_mb
+                        );
+                    } else {
+                        info!(
+                            "Memory recovered: {}MB available, resuming file watching",
+                            available_mb
+                        );
+                    }
+                    paused.store(pause, Ordering::Relaxed);
+                }
+            }
+
+            thread::sleep(MEMORY_CHECK_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paused_state_change() {
+        assert_eq!(paused_state_change(false, 100, 256), Some(true));
+        assert_eq!(paused_state_change(false, 256, 256), None);
+        // stays paused until memory recovers above twice the threshold
+        assert_eq!(paused_state_change(true, 300, 256), None);
+        assert_eq!(paused_state_change(true, 512, 256), None);
+        assert_eq!(paused_state_change(true, 513, 256), Some(false));
+        assert_eq!(paused_state_change(true, 100, 256), None);
+        assert_eq!(paused_state_change(false, 0, 0), None);
+        assert_eq!(paused_state_change(true, 1, u64::MAX), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_available_memory_mb() {
+        assert!(available_memory_mb().is_some_and(|available_mb| available_mb > 0));
+    }
+}
@@ -1,49 +1,1079 @@
+use std::borrow::Cow;
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::RwLock;
+
+/// String similarity metric used to compare the current file name with candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimilarityAlgorithm {
+    JaroWinkler,
+    Levenshtein,
+    DamerauLevenshtein,
+    Sorensen,
+}
+
+impl SimilarityAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace(['-', '_'], "").as_str() {
+            "jarowinkler" => Some(Self::JaroWinkler),
+            "levenshtein" => Some(Self::Levenshtein),
+            "dameraulevenshtein" => Some(Self::DamerauLevenshtein),
+            "sorensen" | "sorensendice" => Some(Self::Sorensen),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::JaroWinkler => "jaro_winkler",
+            Self::Levenshtein => "levenshtein",
+            Self::DamerauLevenshtein => "damerau_levenshtein",
+            Self::Sorensen => "sorensen",
+        }
+    }
+
+    /// Normalized similarity in the `[0.0, 1.0]` range
+    pub fn similarity(self, a: &str, b: &str) -> f64 {
+        match self {
+            Self::JaroWinkler => strsim::jaro_winkler(a, b),
+            Self::Levenshtein => strsim::normalized_levenshtein(a, b),
+            Self::DamerauLevenshtein => strsim::normalized_damerau_levenshtein(a, b),
+            Self::Sorensen => strsim::sorensen_dice(a, b),
+        }
+    }
+}
+
+pub static SIMILARITY_ALGORITHM: RwLock<SimilarityAlgorithm> =
+    RwLock::new(SimilarityAlgorithm::JaroWinkler);
+
+const SIMILARITY_THRESHOLD: f64 = 0.8;
+const MAX_SIMILARITY_BONUS: f64 = 20.0;
+/// Most the Winkler prefix boost adds to the Jaro similarity: four prefix characters at 0.1
+/// of the remainder each
+const MAX_WINKLER_PREFIX_BOOST: f64 = 0.4;
+/// Stems this many times longer than the other one stay below `SIMILARITY_THRESHOLD` with
+/// every algorithm, e.g. Jaro can't exceed 0.7 and gets no Winkler prefix boost. Rejecting
+/// smaller differences would drop pairs like `user` and `user_test`.
+const MIN_DISSIMILAR_LENGTH_RATIO: usize = 10;
+
+/// File name without the extension(s), `user.test.ts` -> `user`
+pub fn file_stem(file_name: &str) -> &str {
+    file_name.split('.').next().unwrap_or(file_name)
+}
+
+/// `similarity_bonus_with_letters` for a single candidate, scoring computes the letters of
+/// the current stem once instead
+#[cfg(test)]
+pub fn calculate_filename_similarity_bonus(
+    current_stem: &str,
+    candidate_stem: &str,
+    algorithm: SimilarityAlgorithm,
+) -> i32 {
+    similarity_bonus_with_letters(
+        current_stem,
+        ascii_letter_mask(current_stem),
+        candidate_stem,
+        algorithm,
+    )
+}
+
+/// Bonus for candidates whose name resembles the current file (e.g. `user.rs` and
+/// `user_test.rs`), scaled from 0 at the similarity threshold up to `MAX_SIMILARITY_BONUS`.
+/// `current_letters` is the `ascii_letter_mask` of `current_stem`, candidates sharing too few
+/// of them to reach the threshold are rejected before the similarity metric runs.
+pub fn similarity_bonus_with_letters(
+    current_stem: &str,
+    current_letters: u32,
+    candidate_stem: &str,
+    algorithm: SimilarityAlgorithm,
+) -> i32 {
+    if current_stem.is_empty()
+        || candidate_stem.is_empty()
+        || is_clearly_dissimilar(current_stem, candidate_stem)
+        || cannot_reach_threshold(
+            current_stem,
+            candidate_stem,
+            current_letters & ascii_letter_mask(candidate_stem),
+            algorithm,
+        )
+    {
+        return 0;
+    }
+
+    let similarity = algorithm.similarity(current_stem, candidate_stem);
+    if similarity < SIMILARITY_THRESHOLD {
+        return 0;
+    }
+
+    let bonus =
+        (similarity - SIMILARITY_THRESHOLD) / (1.0 - SIMILARITY_THRESHOLD) * MAX_SIMILARITY_BONUS;
+    bonus.round() as i32
+}
+
+/// Cheap checks rejecting most unrelated stems before the similarity metric runs: stems at
+/// least `MIN_DISSIMILAR_LENGTH_RATIO` times as long as the other one, and stems sharing none
+/// of the characters either one starts with
+fn is_clearly_dissimilar(a: &str, b: &str) -> bool {
+    let (len_a, len_b) = (a.chars().count(), b.chars().count());
+    if len_a.max(len_b) >= len_a.min(len_b) * MIN_DISSIMILAR_LENGTH_RATIO {
+        return true;
+    }
+
+    let shares_leading_char =
+        |stem: &str, other: &str| other.chars().take(2).any(|leading| stem.contains(leading));
+    !shares_leading_char(a, b) && !shares_leading_char(b, a)
+}
+
+/// Set of the ASCII letters in `stem` regardless of case, one bit per letter
+pub fn ascii_letter_mask(stem: &str) -> u32 {
+    stem.bytes()
+        .filter(u8::is_ascii_alphabetic)
+        .fold(0, |mask, byte| mask | ascii_letter_bit(byte))
+}
+
+#[inline]
+fn ascii_letter_bit(byte: u8) -> u32 {
+    1 << (byte.to_ascii_lowercase() - b'a')
+}
+
+/// Whether the similarity of the stems is provably below `SIMILARITY_THRESHOLD` given the
+/// letters they share. A character missing from the other stem never matches, so at most the
+/// remaining characters do. Any other character than an ASCII letter is assumed to match and
+/// letters are compared regardless of case, which keeps the bound above the real similarity.
+/// Sorensen-Dice compares bigrams and is never rejected.
+fn cannot_reach_threshold(
+    a: &str,
+    b: &str,
+    shared_letters: u32,
+    algorithm: SimilarityAlgorithm,
+) -> bool {
+    let matchable = |stem: &str| {
+        stem.chars()
+            .filter(|c| {
+                !c.is_ascii_alphabetic() || shared_letters & ascii_letter_bit(*c as u8) != 0
+            })
+            .count() as f64
+    };
+    let (len_a, len_b) = (a.chars().count() as f64, b.chars().count() as f64);
+    let (matchable_a, matchable_b) = (matchable(a), matchable(b));
+
+    let upper_bound = match algorithm {
+        SimilarityAlgorithm::JaroWinkler => {
+            // Jaro with every matchable character matching and no transpositions
+            let matches = matchable_a.min(matchable_b);
+            let jaro = (matches / len_a + matches / len_b + 1.0) / 3.0;
+            jaro + MAX_WINKLER_PREFIX_BOOST * (1.0 - jaro)
+        }
+        SimilarityAlgorithm::Levenshtein | SimilarityAlgorithm::DamerauLevenshtein => {
+            // every unmatchable character takes an edit of its own
+            let edits = (len_a - matchable_a).max(len_b - matchable_b);
+            1.0 - edits / len_a.max(len_b)
+        }
+        SimilarityAlgorithm::Sorensen => return false,
+    };
+    // the tolerance keeps rounding from rejecting a bound sitting exactly at the threshold
+    upper_bound + 1e-9 < SIMILARITY_THRESHOLD
+}
+
+/// Case insensitive comparison, Unicode aware for non-ASCII strings like `Ñ` and `ñ`
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+    if a.is_ascii() && b.is_ascii() {
+        return a.eq_ignore_ascii_case(b);
+    }
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// `relative_to` without the warning, for callers that expect paths outside of `base`
+pub fn relative_within(path: &Path, base: &Path) -> Option<String> {
+    let relative = pathdiff::diff_paths(path, base)?;
+    if relative.components().next() == Some(Component::ParentDir) {
+        return None;
+    }
+
+    Some(relative.to_string_lossy().into_owned())
+}
+
+/// Relativize `path` against `base`, returns `None` for paths that are not inside `base`
+pub fn relative_to(path: &Path, base: &Path) -> Option<String> {
+    let relative = relative_within(path, base);
+    if relative.is_none() {
+        tracing::warn!(
+            "Path {} is outside of base path {}",
+            path.display(),
+            base.display()
+        );
+    }
+    relative
+}
+
+const ELLIPSIS: &str = "…";
+
+/// Shortens `path` to at most `max_width` characters by replacing directories with `…`.
+/// The first directory is kept as long as some directory next to the file name fits too, e.g.
+/// `packages/…/handlers/oauth.rs`, then `…/handlers/oauth.rs`. The file name is returned as is
+/// when even `…/name` does not fit.
+pub fn shorten_path(path: &str, max_width: usize) -> String {
+    let fits = |candidate: &str| candidate.chars().count() <= max_width;
+    if fits(path) {
+        return path.to_string();
+    }
+
+    let components: Vec<&str> = split_path_components(path).collect();
+    let Some((&file_name, directories)) = components.split_last() else {
+        return path.to_string();
+    };
+
+    let tail = |kept: usize| {
+        directories[directories.len() - kept..]
+            .iter()
+            .chain(std::iter::once(&file_name))
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+
+    if let Some((first, _)) = directories.split_first() {
+        for kept in (1..directories.len() - 1).rev() {
+            let candidate = format!("{}/{}/{}", first, ELLIPSIS, tail(kept));
+            if fits(&candidate) {
+                return candidate;
+            }
+        }
+    }
+
+    (0..directories.len())
+        .rev()
+        .map(|kept| format!("{}/{}", ELLIPSIS, tail(kept)))
+        .find(|candidate| fits(candidate))
+        .unwrap_or_else(|| file_name.to_string())
+}
+
+/// `path` without the leading `prefix` directory, e.g. `src/api/user.rs` becomes `user.rs`
+/// for the prefix `src/api`. Only whole components are stripped, `path` is returned as is
+/// when it is not inside `prefix`. Both are compared with normalized separators.
+pub fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> &'a str {
+    let prefix = normalize_separators(prefix);
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        return path;
+    }
+
+    // normalizing swaps single byte separators, so the rest has the same length in `path`
+    match normalize_separators(path)
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix('/'))
+    {
+        Some(rest) if !rest.is_empty() => &path[path.len() - rest.len()..],
+        _ => path,
+    }
+}
+
+/// Index of the first path component that starts with `anchor`, which must be lowercased
+pub fn anchor_component_index(relative_path: &str, anchor: &str) -> Option<usize> {
+    relative_path
+        .split(['/', '\\'])
+        .position(|component| component.to_lowercase().starts_with(anchor))
+}
+
+const TEST_DIRECTORIES: [&str; 6] = ["tests", "test", "spec", "__tests__", "integration", "e2e"];
+
+/// Whether any directory component of the path is a conventional test directory
+pub fn is_in_test_directory(relative_path: &str) -> bool {
+    Path::new(relative_path)
+        .parent()
+        .is_some_and(|parent| has_directory_component(parent, is_test_directory))
+}
+
+fn is_test_directory(name: &str) -> bool {
+    TEST_DIRECTORIES.contains(&name) || name.starts_with("test_")
+}
+
+/// Whether any normal component of `directory` satisfies `is_match`
+fn has_directory_component(directory: &Path, is_match: impl Fn(&str) -> bool) -> bool {
+    directory.components().any(|component| match component {
+        Component::Normal(name) => is_match(&name.to_string_lossy()),
+        _ => false,
+    })
+}
+
+/// Whether the file name follows a common test naming convention
+pub fn is_test_file_name(file_name: &str) -> bool {
+    let stem = file_name.split('.').next().unwrap_or(file_name);
+    stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_spec")
+        || file_name.contains(".test.")
+        || file_name.contains(".spec.")
+}
+
+const CONFIG_FILE_NAMES: [&str; 28] = [
+    "dockerfile",
+    "docker-compose.yml",
+    "docker-compose.yaml",
+    "makefile",
+    "justfile",
+    "cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "gemfile",
+    "gemfile.lock",
+    "procfile",
+    "vagrantfile",
+    "nginx.conf",
+    "tsconfig.json",
+    "jsconfig.json",
+    ".gitignore",
+    ".gitattributes",
+    ".gitmodules",
+    ".dockerignore",
+    ".editorconfig",
+    ".prettierrc",
+    ".eslintrc",
+    ".babelrc",
+    ".npmrc",
+    ".nvmrc",
+    ".envrc",
+];
+
+const CONFIG_FILE_EXTENSIONS: [&str; 12] = [
+    "toml",
+    "yaml",
+    "yml",
+    "json",
+    "jsonc",
+    "ini",
+    "cfg",
+    "conf",
+    "env",
+    "lock",
+    "properties",
+    "plist",
+];
+
+/// AppleDouble files like `._main.rs` that macOS writes next to files on non-HFS volumes to
+/// keep their resource fork
+pub fn is_macos_resource_fork(name: &str) -> bool {
+    name.starts_with("._")
+}
+
+/// NTFS alternate data streams like `notes.txt:Zone.Identifier`, a `:` can't appear in file
+/// names on Windows. Always `false` elsewhere.
+pub fn is_ntfs_ads(name: &str) -> bool {
+    cfg!(target_os = "windows") && name.contains(':')
+}
+
+/// Dot files, and on Windows also files with the hidden attribute when `metadata` is given
+pub fn is_hidden_file(name: &str, metadata: Option<&std::fs::Metadata>) -> bool {
+    if name.starts_with('.') {
+        return true;
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        metadata.is_some_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+/// Inode number of the file on unix, 0 on platforms without inodes
+pub fn file_inode(metadata: &std::fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.ino()
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        0
+    }
+}
+
+/// Whether the file is a project configuration file rather than source code
+pub fn detect_config_file(name: &str, extension: &str) -> bool {
+    let name = name.to_lowercase();
+    CONFIG_FILE_NAMES.contains(&name.as_str())
+        || name == ".env"
+        || name.starts_with(".env.")
+        || CONFIG_FILE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+}
+
+/// Kind of file a search result is, for grouping results in the picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCategory {
+    Source,
+    Test,
+    Config,
+    Documentation,
+    Migration,
+    Schema,
+    Asset,
+    Generated,
+    Other,
+}
+
+impl FileCategory {
+    /// Inverse of `name`, only needed to deserialize `FileItem::category`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "source" => Some(Self::Source),
+            "test" => Some(Self::Test),
+            "config" => Some(Self::Config),
+            "documentation" => Some(Self::Documentation),
+            "migration" => Some(Self::Migration),
+            "schema" => Some(Self::Schema),
+            "asset" => Some(Self::Asset),
+            "generated" => Some(Self::Generated),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Source => "source",
+            Self::Test => "test",
+            Self::Config => "config",
+            Self::Documentation => "documentation",
+            Self::Migration => "migration",
+            Self::Schema => "schema",
+            Self::Asset => "asset",
+            Self::Generated => "generated",
+            Self::Other => "other",
+        }
+    }
+}
+
+const GENERATED_DIRECTORIES: [&str; 5] = ["dist", "build", "out", "target", "node_modules"];
+const MIGRATION_DIRECTORIES: [&str; 2] = ["migrations", "migrate"];
+const DOCUMENTATION_DIRECTORIES: [&str; 2] = ["docs", "doc"];
+const DOCUMENTATION_EXTENSIONS: [&str; 5] = ["md", "markdown", "rst", "adoc", "txt"];
+const SCHEMA_EXTENSIONS: [&str; 6] = ["graphql", "gql", "proto", "avro", "avsc", "xsd"];
+const ASSET_EXTENSIONS: [&str; 13] = [
+    "png", "jpg", "jpeg", "gif", "bmp", "svg", "ico", "webp", "woff", "woff2", "ttf", "otf", "mp3",
+];
+/// Source extensions besides the ones of the languages in `LANGUAGE_EXTENSIONS`
+const SOURCE_EXTENSIONS: [&str; 17] = [
+    "sh", "bash", "zsh", "fish", "vim", "zig", "scala", "hs", "ml", "dart", "erl", "clj", "vue",
+    "svelte", "html", "css", "scss",
+];
+
+/// Groups a file by name, extension and its directory relative to the project root. Earlier
+/// checks win, a `.sql` file in a test directory is a test.
+pub fn categorize(name: &str, ext: &str, dir: &str) -> FileCategory {
+    let ext = ext.to_lowercase();
+    let ext = ext.as_str();
+    let dir = Path::new(dir);
+
+    if name.contains(".generated.")
+        || has_directory_component(dir, |name| GENERATED_DIRECTORIES.contains(&name))
+    {
+        FileCategory::Generated
+    } else if is_test_file_name(name) || has_directory_component(dir, is_test_directory) {
+        FileCategory::Test
+    } else if ext == "sql"
+        || has_directory_component(dir, |name| MIGRATION_DIRECTORIES.contains(&name))
+    {
+        FileCategory::Migration
+    } else if SCHEMA_EXTENSIONS.contains(&ext) {
+        FileCategory::Schema
+    } else if ASSET_EXTENSIONS.contains(&ext) {
+        FileCategory::Asset
+    } else if detect_config_file(name, ext) {
+        FileCategory::Config
+    } else if DOCUMENTATION_EXTENSIONS.contains(&ext)
+        || has_directory_component(dir, |name| DOCUMENTATION_DIRECTORIES.contains(&name))
+    {
+        FileCategory::Documentation
+    } else if SOURCE_EXTENSIONS.contains(&ext)
+        || LANGUAGE_EXTENSIONS
+            .iter()
+            .any(|(_, extensions)| extensions.contains(&ext))
+    {
+        FileCategory::Source
+    } else {
+        FileCategory::Other
+    }
+}
+
+/// Files larger than this are not worth estimating, they are rarely read line by line
+const MAX_LINE_ESTIMATE_SIZE: u64 = 10 * 1024 * 1024;
+
+const BINARY_EXTENSIONS: [&str; 24] = [
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "pdf", "zip", "gz", "tar", "xz", "7z", "so",
+    "dylib", "dll", "exe", "o", "a", "class", "jar", "wasm", "woff", "woff2",
+];
+
+/// Average line length in bytes for an extension, used to estimate line counts from file sizes
+fn average_bytes_per_line(extension: &str) -> u64 {
+    match extension {
+        "json" | "csv" | "tsv" => 20,
+        "py" | "rb" | "lua" | "sh" => 35,
+        "rs" | "go" | "c" | "h" | "cpp" | "hpp" | "java" | "kt" => 40,
+        "ts" | "tsx" | "js" | "jsx" => 38,
+        "md" | "txt" | "rst" => 60,
+        _ => 40,
+    }
+}
+
+/// Approximate number of lines of a text file without reading it, 0 for binary or large files
+pub fn estimate_lines_from_size(size: u64, extension: &str) -> u32 {
+    let extension = extension.to_lowercase();
+    if size > MAX_LINE_ESTIMATE_SIZE || BINARY_EXTENSIONS.contains(&extension.as_str()) {
+        return 0;
+    }
+
+    size.div_ceil(average_bytes_per_line(&extension)) as u32
+}
+
+const LANGUAGE_EXTENSIONS: [(&str, &[&str]); 15] = [
+    ("rust", &["rs"]),
+    ("python", &["py", "pyi"]),
+    ("typescript", &["ts", "tsx", "mts", "cts"]),
+    ("javascript", &["js", "jsx", "mjs", "cjs"]),
+    ("go", &["go"]),
+    ("lua", &["lua"]),
+    ("ruby", &["rb"]),
+    ("java", &["java"]),
+    ("kotlin", &["kt", "kts"]),
+    ("c", &["c", "h"]),
+    ("cpp", &["cpp", "cc", "cxx", "hpp", "hh"]),
+    ("csharp", &["cs"]),
+    ("php", &["php"]),
+    ("swift", &["swift"]),
+    ("elixir", &["ex", "exs"]),
+];
+
+/// Share of all files a language needs to be considered the dominant project language
+const DOMINANT_LANGUAGE_RATIO: f64 = 0.4;
+
+/// Dominant language of a project given the extensions of all its files
+pub fn detect_project_language<'a>(
+    extensions: impl Iterator<Item = &'a str>,
+) -> Option<&'static str> {
+    let mut total = 0usize;
+    let mut counts = [0usize; LANGUAGE_EXTENSIONS.len()];
+    for extension in extensions {
+        total += 1;
+        if let Some(index) = LANGUAGE_EXTENSIONS
+            .iter()
+            .position(|(_, language_extensions)| language_extensions.contains(&extension))
+        {
+            counts[index] += 1;
+        }
+    }
+
+    let (index, count) = counts.iter().enumerate().max_by_key(|(_, count)| **count)?;
+    (total > 0 && *count as f64 > total as f64 * DOMINANT_LANGUAGE_RATIO)
+        .then_some(LANGUAGE_EXTENSIONS[index].0)
+}
+
+/// File extensions belonging to a language returned by `detect_project_language`
+pub fn language_extensions(language: &str) -> &'static [&'static str] {
+    LANGUAGE_EXTENSIONS
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map_or(&[], |(_, extensions)| extensions)
+}
+
+/// Distance penalty between two file paths, scoring uses the pre-split variant below
+#[cfg(test)]
 pub fn calculate_distance_penalty(current_file: Option<&str>, candidate_path: &str) -> i32 {
-    let Some(ref current_path) = current_file else {
+    let Some(current_path) = current_file.map(normalize_separators) else {
         return 0; // No penalty if no current file
     };
+    let candidate_path = normalize_separators(candidate_path);
 
-    let current_dir = if let Some(parent) = std::path::Path::new(current_path).parent() {
+    let current_dir = if let Some(parent) = Path::new(current_path.as_ref()).parent() {
         parent.to_string_lossy().to_string()
     } else {
         String::new()
     };
 
-    let candidate_dir = if let Some(parent) = std::path::Path::new(candidate_path).parent() {
+    let candidate_dir = if let Some(parent) = Path::new(candidate_path.as_ref()).parent() {
         parent.to_string_lossy().to_string()
     } else {
         String::new()
     };
 
-    if current_dir == candidate_dir {
-        return 0; // Same directory, no penalty
+    let current_parts: Vec<&str> = split_path_components(&current_dir).collect();
+    calculate_directory_distance_penalty(&current_parts, &candidate_dir)
+}
+
+/// Windows paths with `\` separators as `/` separated ones, borrowed as is on other platforms
+/// where a backslash is a valid file name character
+pub fn normalize_separators(path: &str) -> Cow<'_, str> {
+    if cfg!(target_os = "windows") && path.contains('\\') {
+        Cow::Owned(path.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(path)
     }
+}
 
-    let current_parts: Vec<&str> = current_dir.split('/').filter(|s| !s.is_empty()).collect();
-    let candidate_parts: Vec<&str> = candidate_dir.split('/').filter(|s| !s.is_empty()).collect();
+/// Non-empty components of a '/' separated path
+pub fn split_path_components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
 
-    let common_len = current_parts
-        .iter()
-        .zip(candidate_parts.iter())
-        .take_while(|(a, b)| a == b)
-        .count();
+/// Distance penalty between the pre-split current directory and a candidate directory,
+/// the candidate is walked without allocating since this runs once per scored file
+pub fn calculate_directory_distance_penalty(current_parts: &[&str], candidate_dir: &str) -> i32 {
+    let candidate_dir = normalize_separators(candidate_dir);
+    let mut candidate_parts = split_path_components(&candidate_dir);
+    let mut common_len = 0;
+    let mut candidate_len = 0;
+    for candidate_part in candidate_parts.by_ref() {
+        candidate_len += 1;
+        if current_parts.get(common_len) != Some(&candidate_part) {
+            break;
+        }
+        common_len += 1;
+    }
+    candidate_len += candidate_parts.count();
 
+    let candidate_depth_from_common = candidate_len - common_len;
     let current_depth_from_common = current_parts.len() - common_len;
-    let candidate_depth_from_common = candidate_parts.len() - common_len;
     let total_distance = current_depth_from_common + candidate_depth_from_common;
 
+    scaled_distance_penalty(total_distance, DISTANCE_PENALTY_PER_LEVEL)
+}
+
+/// Whether `candidate_dir` is the directory made of `current_parts` or one below it
+pub fn is_within_directory(current_parts: &[&str], candidate_dir: &str) -> bool {
+    let candidate_dir = normalize_separators(candidate_dir);
+    let mut candidate_parts = split_path_components(&candidate_dir);
+    current_parts
+        .iter()
+        .all(|part| candidate_parts.next() == Some(*part))
+}
+
+const DISTANCE_PENALTY_PER_LEVEL: i32 = -2;
+/// Default of `MAX_PENALTY_LEVELS`
+pub const MAX_PENALTY_LEVEL_MULTIPLIER: i32 = 10;
+
+/// The distance penalty stops growing after this many directory levels
+pub static MAX_PENALTY_LEVELS: AtomicI32 = AtomicI32::new(MAX_PENALTY_LEVEL_MULTIPLIER);
+
+/// `penalty_per_level` for every directory level between two paths, capped at
+/// `MAX_PENALTY_LEVELS` levels. Saturates instead of overflowing for extreme values.
+fn scaled_distance_penalty(total_distance: usize, penalty_per_level: i32) -> i32 {
+    capped_distance_penalty(
+        total_distance,
+        penalty_per_level,
+        MAX_PENALTY_LEVELS.load(Ordering::Relaxed),
+    )
+}
+
+fn capped_distance_penalty(total_distance: usize, penalty_per_level: i32, max_levels: i32) -> i32 {
     if total_distance == 0 {
         return 0; // Same path
     }
 
-    let penalty = -(total_distance as i32 * 2);
-
-    penalty.max(-20)
+    let levels = i32::try_from(total_distance)
+        .unwrap_or(i32::MAX)
+        .min(max_levels.max(0));
+    penalty_per_level.saturating_mul(levels)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_relative_to() {
+        let base = Path::new("/home/user/project");
+        assert_eq!(
+            relative_to(Path::new("/home/user/project/src/main.rs"), base).map(PathBuf::from),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(
+            relative_to(Path::new("/home/user/project/Cargo.toml"), base).map(PathBuf::from),
+            Some(PathBuf::from("Cargo.toml"))
+        );
+        assert_eq!(
+            relative_to(Path::new("/home/user/other/file.rs"), base),
+            None
+        );
+        assert_eq!(relative_to(Path::new("/etc/passwd"), base), None);
+    }
+
+    #[test]
+    fn test_scaled_distance_penalty_saturates() {
+        assert_eq!(scaled_distance_penalty(0, -2), 0);
+        assert_eq!(scaled_distance_penalty(3, -2), -6);
+        assert_eq!(scaled_distance_penalty(usize::MAX, -2), -20);
+        assert_eq!(scaled_distance_penalty(usize::MAX, -100), -1000);
+        assert_eq!(scaled_distance_penalty(usize::MAX, i32::MIN), i32::MIN);
+        assert_eq!(scaled_distance_penalty(5, i32::MIN / 2), i32::MIN);
+    }
+
+    #[test]
+    fn test_capped_distance_penalty() {
+        assert_eq!(
+            capped_distance_penalty(20, -2, MAX_PENALTY_LEVEL_MULTIPLIER),
+            -20
+        );
+        assert_eq!(capped_distance_penalty(20, -20, 3), -60);
+        assert_eq!(capped_distance_penalty(2, -20, 3), -40);
+        assert_eq!(capped_distance_penalty(20, -20, 100), -400);
+        // a cap of 0 or below disables the penalty
+        assert_eq!(capped_distance_penalty(20, -20, 0), 0);
+        assert_eq!(capped_distance_penalty(20, -20, -5), 0);
+    }
+
+    #[test]
+    fn test_ascii_letter_mask() {
+        assert_eq!(ascii_letter_mask(""), 0);
+        assert_eq!(ascii_letter_mask("ab"), 0b11);
+        assert_eq!(ascii_letter_mask("Ba_2z"), ascii_letter_mask("abz"));
+        assert_eq!(ascii_letter_mask("abz").count_ones(), 3);
+
+        let rejects = |a: &str, b: &str, algorithm| {
+            cannot_reach_threshold(a, b, ascii_letter_mask(a) & ascii_letter_mask(b), algorithm)
+        };
+        let jaro_winkler = SimilarityAlgorithm::JaroWinkler;
+        // `user` shares only `u` with `unityx`
+        assert!(rejects("user", "unityx", jaro_winkler));
+        assert!(!rejects("user", "users_test", jaro_winkler));
+        assert!(!rejects("2024", "2025", jaro_winkler));
+        assert!(rejects("user", "unityx", SimilarityAlgorithm::Levenshtein));
+        assert!(!rejects("user", "unityx", SimilarityAlgorithm::Sorensen));
+
+        // the bound never rejects what the metric accepts
+        let stems = [
+            "user",
+            "users_test",
+            "migration",
+            "mig",
+            "api",
+            "api_v2",
+            "main",
+            "Main",
+        ];
+        for algorithm in [
+            jaro_winkler,
+            SimilarityAlgorithm::Levenshtein,
+            SimilarityAlgorithm::DamerauLevenshtein,
+        ] {
+            for a in stems {
+                for b in stems {
+                    if algorithm.similarity(a, b) >= SIMILARITY_THRESHOLD {
+                        assert!(!rejects(a, b, algorithm), "{} {} {:?}", a, b, algorithm);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_clearly_dissimilar() {
+        assert!(!is_clearly_dissimilar("ab", "ab_cdefghijklmnopqr"));
+        assert!(is_clearly_dissimilar("ab", "ab_cdefghijklmnopqrs"));
+        assert!(is_clearly_dissimilar("ab_cdefghijklmnopqrs", "ab"));
+        assert!(!is_clearly_dissimilar("user", "user_test"));
+
+        assert!(!is_clearly_dissimilar("user", "ruse"));
+        assert!(is_clearly_dissimilar("user", "main"));
+    }
+
+    #[test]
+    fn test_calculate_filename_similarity_bonus() {
+        let jaro_winkler = SimilarityAlgorithm::JaroWinkler;
+        assert_eq!(
+            calculate_filename_similarity_bonus("", "user", jaro_winkler),
+            0
+        );
+        assert_eq!(
+            calculate_filename_similarity_bonus("user", "user", jaro_winkler),
+            20
+        );
+        assert!(calculate_filename_similarity_bonus("user", "user_test", jaro_winkler) > 0);
+        assert_eq!(
+            calculate_filename_similarity_bonus("user", "router", jaro_winkler),
+            0
+        );
+        assert_eq!(
+            calculate_filename_similarity_bonus(
+                "a",
+                "index_router_middleware_factory",
+                jaro_winkler
+            ),
+            0
+        );
+        assert_eq!(
+            calculate_filename_similarity_bonus("user", "main", jaro_winkler),
+            0
+        );
+        // shares three of the eight letters of `migration` but is similar enough
+        assert_eq!(
+            calculate_filename_similarity_bonus("migration", "mig", jaro_winkler),
+            4
+        );
+
+        // the suffix is ignored by jaro winkler but not by levenshtein
+        assert!(calculate_filename_similarity_bonus("api", "api_v2", jaro_winkler) > 0);
+        assert_eq!(
+            calculate_filename_similarity_bonus("api", "api_v2", SimilarityAlgorithm::Levenshtein),
+            0
+        );
+    }
+
+    #[test]
+    fn test_shorten_path() {
+        let path = "packages/platform/server/src/domain/auth/handlers/oauth.rs";
+        assert_eq!(shorten_path(path, 100), path);
+        assert_eq!(
+            shorten_path(path, 40),
+            "packages/…/domain/auth/handlers/oauth.rs"
+        );
+        assert_eq!(shorten_path(path, 33), "packages/…/auth/handlers/oauth.rs");
+        assert_eq!(shorten_path(path, 32), "packages/…/handlers/oauth.rs");
+        assert_eq!(shorten_path(path, 20), "…/handlers/oauth.rs");
+        assert_eq!(shorten_path(path, 10), "…/oauth.rs");
+        assert_eq!(shorten_path(path, 5), "oauth.rs");
+        assert_eq!(shorten_path("src/main.rs", 3), "main.rs");
+        assert_eq!(shorten_path("main.rs", 3), "main.rs");
+    }
+
+    #[test]
+    fn test_anchor_component_index() {
+        assert_eq!(anchor_component_index("src/main.rs", "src"), Some(0));
+        assert_eq!(
+            anchor_component_index("crates/core/Src/lib.rs", "src"),
+            Some(2)
+        );
+        assert_eq!(anchor_component_index("lua/fff/main.lua", "ma"), Some(2));
+        assert_eq!(anchor_component_index("resources/main.rs", "src"), None);
+        assert_eq!(anchor_component_index("README.md", "readme"), Some(0));
+    }
+
+    #[test]
+    fn test_estimate_lines_from_size() {
+        assert_eq!(estimate_lines_from_size(4000, "rs"), 100);
+        assert_eq!(estimate_lines_from_size(4000, "JSON"), 200);
+        assert_eq!(estimate_lines_from_size(4000, "png"), 0);
+        assert_eq!(estimate_lines_from_size(0, "rs"), 0);
+        assert_eq!(
+            estimate_lines_from_size(MAX_LINE_ESTIMATE_SIZE + 1, "md"),
+            0
+        );
+    }
+
+    #[test]
+    fn test_detect_project_language() {
+        let rust = ["rs", "rs", "rs", "toml", "md"];
+        assert_eq!(detect_project_language(rust.into_iter()), Some("rust"));
+        assert_eq!(language_extensions("rust"), &["rs"]);
+
+        let mixed = ["rs", "py", "ts", "md", "lua"];
+        assert_eq!(detect_project_language(mixed.into_iter()), None);
+        assert_eq!(detect_project_language(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_is_hidden_file() {
+        assert!(is_hidden_file(".gitignore", None));
+        assert!(is_hidden_file(".env", None));
+        assert!(!is_hidden_file("main.rs", None));
+        assert!(!is_hidden_file("file.", None));
+    }
+
+    #[test]
+    fn test_detect_config_file() {
+        assert!(detect_config_file("Cargo.toml", "toml"));
+        assert!(detect_config_file("Dockerfile", ""));
+        assert!(detect_config_file(".env.local", "local"));
+        assert!(detect_config_file("nginx.conf", "conf"));
+        assert!(detect_config_file("settings.YAML", "YAML"));
+        assert!(!detect_config_file("main.rs", "rs"));
+        assert!(!detect_config_file("environment.ts", "ts"));
+    }
+
+    #[test]
+    fn test_categorize() {
+        let category = |path: &str| {
+            let path = Path::new(path);
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let ext = path.extension().map_or("", |ext| ext.to_str().unwrap());
+            let dir = path.parent().unwrap().to_str().unwrap();
+            categorize(name, ext, dir)
+        };
+
+        assert_eq!(category("src/main.rs"), FileCategory::Source);
+        assert_eq!(category("lua/fff/init.lua"), FileCategory::Source);
+        assert_eq!(category("src/parser_test.go"), FileCategory::Test);
+        assert_eq!(category("tests/fixtures/data.json"), FileCategory::Test);
+        assert_eq!(category("Cargo.toml"), FileCategory::Config);
+        assert_eq!(category(".github/workflows/ci.yaml"), FileCategory::Config);
+        assert_eq!(category("README.md"), FileCategory::Documentation);
+        assert_eq!(
+            category("docs/guide/intro.html"),
+            FileCategory::Documentation
+        );
+        assert_eq!(
+            category("db/migrations/0001_init.py"),
+            FileCategory::Migration
+        );
+        assert_eq!(category("queries/users.SQL"), FileCategory::Migration);
+        assert_eq!(category("api/user.proto"), FileCategory::Schema);
+        assert_eq!(category("schema.graphql"), FileCategory::Schema);
+        assert_eq!(category("assets/logo.svg"), FileCategory::Asset);
+        assert_eq!(category("favicon.ico"), FileCategory::Asset);
+        assert_eq!(category("src/api.generated.ts"), FileCategory::Generated);
+        assert_eq!(category("dist/bundle.js"), FileCategory::Generated);
+        assert_eq!(category("build/docs/index.md"), FileCategory::Generated);
+        assert_eq!(category("LICENSE"), FileCategory::Other);
+
+        for category in [
+            FileCategory::Source,
+            FileCategory::Generated,
+            FileCategory::Other,
+        ] {
+            assert_eq!(FileCategory::from_name(category.name()), Some(category));
+        }
+    }
+
+    #[test]
+    fn test_is_in_test_directory() {
+        assert!(is_in_test_directory("tests/integration.rs"));
+        assert!(is_in_test_directory("test/helper.py"));
+        assert!(is_in_test_directory("spec/models/user_spec.rb"));
+        assert!(is_in_test_directory("src/__tests__/button.tsx"));
+        assert!(is_in_test_directory("integration/api.go"));
+        assert!(is_in_test_directory("crates/core/tests/fixtures/data.json"));
+        assert!(is_in_test_directory("test_utils/mock.py"));
+        assert!(is_in_test_directory("src/test_helpers/setup.ts"));
+        assert!(is_in_test_directory("e2e/login.ts"));
+
+        assert!(!is_in_test_directory("mytest_utils/mock.py"));
+        assert!(!is_in_test_directory("src/testing/mod.rs"));
+        assert!(!is_in_test_directory("src/contest/main.rs"));
+        assert!(!is_in_test_directory("tests.rs"));
+        assert!(!is_in_test_directory("src/tests.rs"));
+        assert!(!is_in_test_directory("test_main.py"));
+        assert!(!is_in_test_directory("specs_old/index.js"));
+        assert!(!is_in_test_directory("latest/app.js"));
+        assert!(!is_in_test_directory(""));
+    }
+
+    #[test]
+    fn test_is_within_directory() {
+        assert!(is_within_directory(&["src", "api"], "src/api"));
+        assert!(is_within_directory(&["src", "api"], "src/api/v1"));
+        assert!(is_within_directory(&[], "docs"));
+
+        assert!(!is_within_directory(&["src", "api"], "src"));
+        assert!(!is_within_directory(&["src", "api"], "src/apis"));
+        assert!(!is_within_directory(&["src", "api"], "lib/api"));
+    }
+
+    #[test]
+    fn test_normalize_separators() {
+        assert!(matches!(
+            normalize_separators("src/api/mod.rs"),
+            Cow::Borrowed("src/api/mod.rs")
+        ));
+        #[cfg(not(target_os = "windows"))]
+        assert_eq!(normalize_separators(r"odd\name.rs"), r"odd\name.rs");
+    }
+
+    #[test]
+    fn test_platform_artifacts() {
+        assert!(is_macos_resource_fork("._main.rs"));
+        assert!(!is_macos_resource_fork(".gitignore"));
+        assert!(!is_macos_resource_fork("main._rs"));
+
+        assert_eq!(
+            is_ntfs_ads("notes.txt:Zone.Identifier"),
+            cfg!(target_os = "windows")
+        );
+        assert!(!is_ntfs_ads("notes.txt"));
+    }
+
+    #[test]
+    fn test_eq_ignore_case() {
+        assert!(eq_ignore_case("README", "readme"));
+        assert!(eq_ignore_case("Ñoño", "ñoño"));
+        assert!(eq_ignore_case("ÄB", "äb"));
+        assert!(!eq_ignore_case("Ñoño", "nono"));
+        assert!(!eq_ignore_case("rs", "r"));
+    }
+
+    #[test]
+    fn test_relative_within() {
+        let base = Path::new("/home/user/project");
+        assert_eq!(
+            relative_within(Path::new("/home/user/project/src/main.rs"), base).map(PathBuf::from),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(relative_within(base, base).as_deref(), Some(""));
+        assert_eq!(
+            relative_within(Path::new("/home/user/other/main.rs"), base),
+            None
+        );
+        assert_eq!(relative_within(Path::new("/mnt/usb"), base), None);
+    }
+
+    #[test]
+    fn test_strip_path_prefix() {
+        assert_eq!(strip_path_prefix("src/api/user.rs", "src/api"), "user.rs");
+        assert_eq!(strip_path_prefix("src/api/user.rs", "src/api/"), "user.rs");
+        assert_eq!(
+            strip_path_prefix("src/api/v1/user.rs", "src"),
+            "api/v1/user.rs"
+        );
+        if cfg!(target_os = "windows") {
+            assert_eq!(strip_path_prefix(r"src\api\user.rs", "src/api"), "user.rs");
+        } else {
+            // backslashes are part of file names outside of windows
+            assert_eq!(
+                strip_path_prefix(r"src\api\user.rs", r"src\api"),
+                r"src\api\user.rs"
+            );
+        }
+
+        // only whole components are stripped
+        assert_eq!(
+            strip_path_prefix("src/apis/user.rs", "src/api"),
+            "src/apis/user.rs"
+        );
+        assert_eq!(strip_path_prefix("lib/user.rs", "src"), "lib/user.rs");
+        assert_eq!(strip_path_prefix("src/api", "src/api"), "src/api");
+        assert_eq!(strip_path_prefix("src/api/user.rs", ""), "src/api/user.rs");
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_distance_penalty_with_backslash_separators() {
+        assert_eq!(normalize_separators(r"src\api\mod.rs"), "src/api/mod.rs");
+        assert_eq!(
+            calculate_distance_penalty(Some(r"src\api\mod.rs"), r"src\api\routes.rs"),
+            0
+        );
+        assert_eq!(
+            calculate_distance_penalty(Some("src/api/mod.rs"), r"src\lib.rs"),
+            -2
+        );
+        assert_eq!(
+            calculate_directory_distance_penalty(&["src", "api"], r"src\api"),
+            0
+        );
+        assert!(is_within_directory(&["src"], r"src\api\v1"));
+    }
+
     #[test]
     fn test_calculate_distance_penalty() {
         assert_eq!(calculate_distance_penalty(None, "/path/to/file.txt"), 0);
@@ -0,0 +1,158 @@
+use crate::git::{format_git_status, is_modified_status};
+use crate::path_utils::{eq_ignore_case, normalize_separators, split_path_components};
+use crate::types::FileItem;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether `@ext:`, `@dir:` and `@git:` tokens in queries filter the searched files
+pub static QUERY_PREFIX_ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryFilter {
+    /// `@ext:rs`, lowercased extension without the leading dot
+    Extension(String),
+    /// `@dir:src/api`, files in the directory or below it
+    Directory(String),
+    /// `@git:modified`, a status as formatted by `format_git_status` or `changed` for any change
+    GitStatus(String),
+}
+
+impl QueryFilter {
+    fn parse(token: &str) -> Option<Self> {
+        let (kind, value) = token.strip_prefix('@')?.split_once(':')?;
+        if value.is_empty() {
+            return None;
+        }
+
+        match kind {
+            "ext" => Some(Self::Extension(
+                value.trim_start_matches('.').to_lowercase(),
+            )),
+            "dir" => Some(Self::Directory(normalize_separators(value).into_owned())),
+            "git" => Some(Self::GitStatus(value.to_lowercase())),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, file: &FileItem) -> bool {
+        match self {
+            Self::Extension(extension) => eq_ignore_case(&file.extension, extension),
+            Self::Directory(directory) => {
+                let file_directory = normalize_separators(&file.directory);
+                let mut file_components = split_path_components(&file_directory);
+                split_path_components(directory)
+                    .all(|component| file_components.next() == Some(component))
+            }
+            Self::GitStatus(status) if status == "changed" => {
+                file.git_status.is_some_and(is_modified_status)
+            }
+            Self::GitStatus(status) => format_git_status(file.git_status) == status,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedQuery<'a> {
+    pub filters: Vec<QueryFilter>,
+    /// The query without the filter tokens, matched fuzzily
+    pub fuzzy_part: Cow<'a, str>,
+}
+
+impl ParsedQuery<'_> {
+    /// Whether a file passes every filter
+    pub fn matches(&self, file: &FileItem) -> bool {
+        self.filters.iter().all(|filter| filter.matches(file))
+    }
+}
+
+/// Splits filter tokens off `query`, they can appear anywhere in it. The query is borrowed as
+/// is when it has no filters.
+pub fn parse_query(query: &str) -> ParsedQuery<'_> {
+    let mut filters = Vec::new();
+    let mut fuzzy_tokens = Vec::new();
+    for token in query.split(' ').filter(|token| !token.is_empty()) {
+        match QueryFilter::parse(token) {
+            Some(filter) => filters.push(filter),
+            None => fuzzy_tokens.push(token),
+        }
+    }
+
+    let fuzzy_part = if filters.is_empty() {
+        Cow::Borrowed(query)
+    } else {
+        Cow::Owned(fuzzy_tokens.join(" "))
+    };
+    ParsedQuery {
+        filters,
+        fuzzy_part,
+    }
+}
+
+/// Directory of the first `@dir:` filter of `query`, none when query filters are disabled
+pub fn directory_filter(query: &str) -> Option<String> {
+    if !QUERY_PREFIX_ENABLED.load(Ordering::Relaxed) || !query.contains("@dir:") {
+        return None;
+    }
+
+    parse_query(query)
+        .filters
+        .into_iter()
+        .find_map(|filter| match filter {
+            QueryFilter::Directory(directory) => Some(directory),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let parsed = parse_query("@ext:rs main");
+        assert_eq!(parsed.filters, [QueryFilter::Extension("rs".to_string())]);
+        assert_eq!(parsed.fuzzy_part, "main");
+
+        let parsed = parse_query("src @dir:lua/fff mod @git:Modified");
+        assert_eq!(
+            parsed.filters,
+            [
+                QueryFilter::Directory("lua/fff".to_string()),
+                QueryFilter::GitStatus("modified".to_string()),
+            ]
+        );
+        assert_eq!(parsed.fuzzy_part, "src mod");
+
+        // unknown or empty filters stay part of the fuzzy query
+        let parsed = parse_query("@foo:bar @ext: user@example");
+        assert!(parsed.filters.is_empty());
+        assert_eq!(parsed.fuzzy_part, "@foo:bar @ext: user@example");
+
+        assert_eq!(
+            parse_query("@ext:.TS").filters,
+            [QueryFilter::Extension("ts".to_string())]
+        );
+        assert_eq!(parse_query("@ext:ts").fuzzy_part, "");
+
+        // a backslash is a file name character outside of Windows
+        let expected = if cfg!(target_os = "windows") {
+            "src/api"
+        } else {
+            "src\\api"
+        };
+        assert_eq!(
+            parse_query("@dir:src\\api").filters,
+            [QueryFilter::Directory(expected.to_string())]
+        );
+    }
+
+    #[test]
+    fn test_directory_filter() {
+        assert_eq!(
+            directory_filter("main @ext:rs @dir:src/api").as_deref(),
+            Some("src/api")
+        );
+        assert_eq!(directory_filter("main @ext:rs"), None);
+        assert_eq!(directory_filter("@dir: main"), None);
+    }
+}
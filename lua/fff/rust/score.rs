@@ -1,11 +1,95 @@
 use crate::{
     git::is_modified_status,
-    path_utils::calculate_distance_penalty,
+    path_utils::{
+        ascii_letter_mask, calculate_directory_distance_penalty, file_stem, is_within_directory,
+        similarity_bonus_with_letters, SimilarityAlgorithm, SIMILARITY_ALGORITHM,
+    },
+    query::{parse_query, ParsedQuery, QUERY_PREFIX_ENABLED},
     types::{FileItem, Score, ScoringContext},
 };
 use rayon::prelude::*;
+use std::borrow::Borrow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default number of files matched together, see `ScoringContext::chunk_size`
+pub const DEFAULT_MATCH_CHUNK_SIZE: usize = 25_000;
+/// Default haystack size below which matching stays sequential, see
+/// `ScoringContext::parallel_threshold`. Spreading fewer files over workers costs more than
+/// matching them on one thread, `bench_parallel_matching` compares both.
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 500;
+/// Queries shorter than this are matched sequentially, they are cheap to match
+const MIN_PARALLEL_QUERY_LENGTH: usize = 3;
+/// Number of paths each worker matches before checking whether it should stop early
+const EARLY_EXIT_CHUNK_SIZE: usize = 1024;
+/// Matches scoring above this count towards `ScoringContext::early_exit_threshold`
+const HIGH_CONFIDENCE_SCORE: u16 = 200;
+/// Subtracted from the base score of matches only found with the raised typo limit
+const PERMISSIVE_FUZZY_PENALTY: i32 = 50;
+/// Typos allowed on top of `ScoringContext::max_typos` when the first match finds nothing
+const PERMISSIVE_EXTRA_TYPOS: u16 = 2;
+/// Multiplier of the access frecency when there is no query to match
+pub const ACCESS_SCORE_WEIGHT: i32 = 1;
+/// Multiplier of the modification frecency when there is no query to match. Without a query
+/// the picker mostly shows what was just worked on, and recently modified or git changed
+/// files are a better guess for that than files that were only opened.
+pub const MODIFICATION_SCORE_WEIGHT: i32 = 4;
+/// Highest score neo_frizbee gives in practice, reached by long queries matching contiguously
+const MAX_MATCH_SCORE: f32 = 255.0;
+/// Percentage of the base score added for files in the current file's directory or below it
+const RELATION_BONUS_PERCENT: i64 = 12;
+/// Confidence of exact, regex and substring matches, they contain the query as it was typed
+const LITERAL_MATCH_CONFIDENCE: f32 = 1.0;
+
+/// Typo limit of searches that don't set one, from the part of the query that is fuzzy
+/// matched. Small queries with a large number of results can match absolutely everything.
+pub fn default_max_typos(query: &str) -> u16 {
+    let fuzzy_len = if QUERY_PREFIX_ENABLED.load(Ordering::Relaxed) && query.contains('@') {
+        parse_query(query).fuzzy_part.len()
+    } else {
+        query.len()
+    };
+    (fuzzy_len as u16 / 4).clamp(2, 6)
+}
 
 pub fn match_and_score_files(files: &[FileItem], context: &ScoringContext) -> Vec<(usize, Score)> {
+    if QUERY_PREFIX_ENABLED.load(Ordering::Relaxed) && context.query.contains('@') {
+        let parsed = parse_query(context.query);
+        if !parsed.filters.is_empty() {
+            return match_and_score_filtered(files, &parsed, context);
+        }
+    }
+
+    match_and_score_query(files, context)
+}
+
+/// Scores only the files passing the query filters, against the rest of the query
+fn match_and_score_filtered(
+    files: &[FileItem],
+    parsed: &ParsedQuery,
+    context: &ScoringContext,
+) -> Vec<(usize, Score)> {
+    let filtered_indices: Vec<usize> = files
+        .par_iter()
+        .enumerate()
+        .filter(|(_, file)| parsed.matches(file))
+        .map(|(idx, _)| idx)
+        .collect();
+    let filtered_files: Vec<&FileItem> = filtered_indices.iter().map(|&idx| &files[idx]).collect();
+
+    let context = ScoringContext {
+        query: &parsed.fuzzy_part,
+        ..context.clone()
+    };
+    match_and_score_query(&filtered_files, &context)
+        .into_iter()
+        .map(|(idx, score)| (filtered_indices[idx], score))
+        .collect()
+}
+
+pub(crate) fn match_and_score_query<F: Borrow<FileItem> + Sync>(
+    files: &[F],
+    context: &ScoringContext,
+) -> Vec<(usize, Score)> {
     if context.query.len() < 2 {
         return score_all_by_frecency(files, context);
     }
@@ -14,25 +98,50 @@ pub fn match_and_score_files(files: &[FileItem], context: &ScoringContext) -> Ve
         return Vec::new();
     }
 
-    let options = neo_frizbee::Options {
+    let mut options = neo_frizbee::Options {
         prefilter: true,
         max_typos: Some(context.max_typos),
         sort: false,
     };
 
-    let haystack: Vec<&str> = files.iter().map(|f| f.relative_path.as_str()).collect();
     tracing::debug!(
         "Starting fuzzy search for query '{}' in {} files",
         context.query,
-        haystack.len()
+        files.len()
     );
-    let path_matches =
-        neo_frizbee::match_list_parallel(context.query, &haystack, options, context.max_threads);
+    let mut path_matches = match_paths(files, options, context);
     tracing::debug!(
         "Matched {} files for query '{}'",
         path_matches.len(),
         context.query
     );
+    if path_matches.is_empty() && !context.allow_fallback {
+        return Vec::new();
+    }
+
+    // retry with a few more typos, then fall back to substrings. The limit stays below the
+    // query length since a query made of typos only matches every file.
+    let permissive_max_typos = context
+        .max_typos
+        .saturating_add(PERMISSIVE_EXTRA_TYPOS)
+        .min(context.query.len().saturating_sub(1) as u16);
+    let is_permissive = path_matches.is_empty() && permissive_max_typos > context.max_typos;
+    if is_permissive {
+        options.max_typos = Some(permissive_max_typos);
+        path_matches = match_paths(files, options, context);
+        tracing::debug!(
+            "Matched {} files for query '{}' with up to {} typos",
+            path_matches.len(),
+            context.query,
+            permissive_max_typos
+        );
+    }
+    if path_matches.is_empty() {
+        let query = context.query.to_lowercase();
+        return score_matching_files(files, context, "substring", |file| {
+            file.borrow().relative_path.to_lowercase().contains(&query)
+        });
+    }
 
     // assume that filename should only match if the path matches
     // we should actually incorporate this bonus by getting this information from neo_frizbee directly
@@ -42,32 +151,37 @@ pub fn match_and_score_files(files: &[FileItem], context: &ScoringContext) -> Ve
         .filter_map(|m| {
             files
                 .get(m.index_in_haystack as usize)
-                .map(|f| f.file_name.as_str())
+                .map(|f| f.borrow().file_name.as_str())
         })
         .collect::<Vec<_>>();
 
-    let mut filename_matches = neo_frizbee::match_list_parallel(
-        context.query,
-        &haystack_of_filenames,
-        options,
-        context.max_threads,
-    );
+    let mut filename_matches = match_list(&haystack_of_filenames, options, context);
 
     filename_matches.par_sort_by_key(|m| m.index_in_haystack);
+    let similarity = SimilarityContext::new(context);
+    let current_dir_parts = current_dir_parts(context);
     let mut next_filename_match_index = 0;
     let mut results: Vec<_> = path_matches
         .into_iter()
         .enumerate()
         .map(|(index, neo_frizbee_match)| {
             let file_idx = neo_frizbee_match.index_in_haystack as usize;
-            let file = &files[file_idx];
+            let file = files[file_idx].borrow();
 
-            let base_score = neo_frizbee_match.score as i32;
-            let frecency_boost = base_score.saturating_mul(file.total_frecency_score as i32) / 100;
-            let distance_penalty = calculate_distance_penalty(
-                context.current_file.map(|s| s.as_str()),
-                &file.relative_path,
+            let base_score = if is_permissive {
+                (neo_frizbee_match.score as i32).saturating_sub(PERMISSIVE_FUZZY_PENALTY)
+            } else {
+                neo_frizbee_match.score as i32
+            };
+            let total_frecency_score = clamp_to_i32(
+                file.total_frecency_score
+                    .saturating_add(calculate_directory_frecency_bonus(file, context).into()),
             );
+            let frecency_boost = percent_of(base_score, total_frecency_score.into());
+            let distance_penalty = calculate_distance_penalty(current_dir_parts, file);
+            let similarity_bonus = similarity.bonus(file);
+            let branch_change_bonus = calculate_branch_change_bonus(file, context);
+            let relation_bonus = calculate_relation_bonus(current_dir_parts, file, base_score);
 
             let filename_match = filename_matches
                 .get(next_filename_match_index)
@@ -92,15 +206,23 @@ pub fn match_and_score_files(files: &[FileItem], context: &ScoringContext) -> Ve
                     // 18% bonus special filename just as much as exact path
                     // but a little bit less to give preference to the actual file if present
                     has_special_filename_bonus = true;
-                    base_score * 18 / 100
+                    percent_of(base_score, 18)
                 }
                 None => 0,
             };
 
             let total = base_score
+                .saturating_add(calculate_extension_affinity_bonus(
+                    file, base_score, context,
+                ))
                 .saturating_add(frecency_boost)
                 .saturating_add(distance_penalty)
-                .saturating_add(filename_bonus);
+                .saturating_add(filename_bonus)
+                .saturating_add(similarity_bonus)
+                .saturating_add(branch_change_bonus)
+                .saturating_add(relation_bonus)
+                .saturating_sub(calculate_config_file_penalty(file, context))
+                .saturating_sub(calculate_hidden_file_penalty(file, context));
 
             let score = Score {
                 total,
@@ -113,11 +235,16 @@ pub fn match_and_score_files(files: &[FileItem], context: &ScoringContext) -> Ve
                 },
                 frecency_boost,
                 distance_penalty,
+                similarity_bonus,
+                branch_change_bonus,
+                relation_bonus,
                 match_type: match filename_match {
+                    _ if is_permissive => "permissive_fuzzy",
                     Some(filename_match) if filename_match.exact => "exact_filename",
                     Some(_) => "fuzzy_filename",
                     None => "fuzzy_path",
                 },
+                confidence: match_confidence(base_score),
             };
 
             (file_idx, score)
@@ -129,6 +256,107 @@ pub fn match_and_score_files(files: &[FileItem], context: &ScoringContext) -> Ve
     results
 }
 
+/// Fuzzy matches the relative paths of all files. Files are matched in chunks of
+/// `context.chunk_size` so the haystack of one chunk is built at a time. The chunks run one
+/// after another since each of them is already spread over `max_threads` workers. When an
+/// early exit threshold is configured smaller chunks are used and the remaining ones are
+/// skipped once enough high confidence matches exist, `max_threads` workers take turns
+/// claiming the next chunk.
+fn match_paths<F: Borrow<FileItem> + Sync>(
+    files: &[F],
+    options: neo_frizbee::Options,
+    context: &ScoringContext,
+) -> Vec<neo_frizbee::Match> {
+    if context.early_exit_threshold == usize::MAX {
+        let chunk_size = context.chunk_size.max(1);
+        return files
+            .chunks(chunk_size)
+            .enumerate()
+            .flat_map(|(chunk_index, chunk)| {
+                let mut matches = match_list(&haystack_of(chunk), options, context);
+                offset_matches(&mut matches, chunk_index * chunk_size);
+                matches
+            })
+            .collect();
+    }
+
+    let high_confidence_matches = AtomicUsize::new(0);
+    let next_chunk = AtomicUsize::new(0);
+    let chunk_count = files.len().div_ceil(EARLY_EXIT_CHUNK_SIZE);
+    // each worker claims the next unmatched chunk until enough high confidence matches exist
+    let match_chunks = || {
+        let mut matches = Vec::new();
+        while high_confidence_matches.load(Ordering::Relaxed) < context.early_exit_threshold {
+            let chunk_index = next_chunk.fetch_add(1, Ordering::Relaxed);
+            if chunk_index >= chunk_count {
+                break;
+            }
+
+            let start = chunk_index * EARLY_EXIT_CHUNK_SIZE;
+            let chunk = &files[start..(start + EARLY_EXIT_CHUNK_SIZE).min(files.len())];
+            let mut chunk_matches =
+                neo_frizbee::match_list(context.query, &haystack_of(chunk), options);
+            let high_confidence = chunk_matches
+                .iter()
+                .filter(|m| m.score > HIGH_CONFIDENCE_SCORE)
+                .count();
+            high_confidence_matches.fetch_add(high_confidence, Ordering::Relaxed);
+
+            offset_matches(&mut chunk_matches, start);
+            matches.append(&mut chunk_matches);
+        }
+        matches
+    };
+
+    let thread_count = context.max_threads.clamp(1, chunk_count.max(1));
+    if thread_count == 1 {
+        return match_chunks();
+    }
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..thread_count)
+            .map(|_| scope.spawn(match_chunks))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+/// Matches the query against `haystack`, spawning workers only for large haystacks and
+/// queries long enough to be worth it
+fn match_list(
+    haystack: &[&str],
+    options: neo_frizbee::Options,
+    context: &ScoringContext,
+) -> Vec<neo_frizbee::Match> {
+    if haystack.len() < context.parallel_threshold
+        || context.query.chars().count() < MIN_PARALLEL_QUERY_LENGTH
+    {
+        return neo_frizbee::match_list(context.query, haystack, options);
+    }
+
+    neo_frizbee::match_list_parallel(context.query, haystack, options, context.max_threads)
+}
+
+fn haystack_of<F: Borrow<FileItem>>(files: &[F]) -> Vec<&str> {
+    files
+        .iter()
+        .map(|f| f.borrow().relative_path.as_str())
+        .collect()
+}
+
+/// Turns chunk relative match indices into indices into the full file list
+fn offset_matches(matches: &mut [neo_frizbee::Match], offset: usize) {
+    for m in matches {
+        m.index_in_haystack += offset as u32;
+    }
+}
+
 /// Check if a filename is a special entry point file that deserves bonus scoring
 /// These are typically files that serve as module exports or entry points
 fn is_special_entry_point_file(filename: &str) -> bool {
@@ -154,36 +382,243 @@ fn is_special_entry_point_file(filename: &str) -> bool {
     )
 }
 
-fn score_all_by_frecency(files: &[FileItem], context: &ScoringContext) -> Vec<(usize, Score)> {
+fn score_all_by_frecency<F: Borrow<FileItem> + Sync>(
+    files: &[F],
+    context: &ScoringContext,
+) -> Vec<(usize, Score)> {
+    let similarity = SimilarityContext::new(context);
+    let current_dir_parts = current_dir_parts(context);
     files
         .par_iter()
         .enumerate()
         .map(|(idx, file)| {
-            let total_frecency_score = file.access_frecency_score as i32
-                + (file.modification_frecency_score as i32).saturating_mul(4);
+            let file = file.borrow();
+            let score = score_without_match(
+                file,
+                context,
+                &similarity,
+                current_dir_parts,
+                ("frecency", 0.0),
+            );
+            (idx, score)
+        })
+        .collect()
+}
 
-            let distance_penalty = calculate_distance_penalty(
-                context.current_file.map(|x| x.as_str()),
-                &file.relative_path,
+/// Files accepted by `is_match` ranked like an empty query, used by the non fuzzy search modes
+/// and as the last resort when the permissive fuzzy match finds nothing. Each of them matched
+/// with `LITERAL_MATCH_CONFIDENCE`.
+pub fn score_matching_files<F: Borrow<FileItem> + Sync>(
+    files: &[F],
+    context: &ScoringContext,
+    match_type: &'static str,
+    is_match: impl Fn(&FileItem) -> bool + Sync,
+) -> Vec<(usize, Score)> {
+    let similarity = SimilarityContext::new(context);
+    let current_dir_parts = current_dir_parts(context);
+    let mut results: Vec<_> = files
+        .par_iter()
+        .enumerate()
+        .filter(|(_, file)| is_match((*file).borrow()))
+        .map(|(idx, file)| {
+            let file = file.borrow();
+            let score = score_without_match(
+                file,
+                context,
+                &similarity,
+                current_dir_parts,
+                (match_type, LITERAL_MATCH_CONFIDENCE),
             );
+            (idx, score)
+        })
+        .collect();
 
-            let total = total_frecency_score
-                .saturating_add(distance_penalty)
-                .saturating_add(calculate_file_bonus(file, context));
+    results.par_sort_by(|a, b| b.1.total.cmp(&a.1.total));
+    results
+}
 
-            let score = Score {
-                total,
-                base_score: 0,
-                filename_bonus: 0,
-                special_filename_bonus: 0,
-                frecency_boost: total_frecency_score,
-                distance_penalty,
-                match_type: "frecency",
-            };
+/// Score of a file from frecency and its relation to the current file alone, with the match
+/// type and confidence of how the file matched
+fn score_without_match(
+    file: &FileItem,
+    context: &ScoringContext,
+    similarity: &SimilarityContext,
+    current_dir_parts: Option<&[&str]>,
+    (match_type, confidence): (&'static str, f32),
+) -> Score {
+    let total_frecency_score = clamp_to_i32(
+        file.access_frecency_score
+            .saturating_mul(context.frecency_weight_access.into())
+            .saturating_add(
+                file.modification_frecency_score
+                    .saturating_mul(context.frecency_weight_modification.into()),
+            )
+            .saturating_add(calculate_directory_frecency_bonus(file, context).into()),
+    );
 
-            (idx, score)
+    let distance_penalty = calculate_distance_penalty(current_dir_parts, file);
+    let similarity_bonus = similarity.bonus(file);
+    let branch_change_bonus = calculate_branch_change_bonus(file, context);
+
+    let total = total_frecency_score
+        .saturating_add(distance_penalty)
+        .saturating_add(similarity_bonus)
+        .saturating_add(branch_change_bonus)
+        .saturating_add(calculate_file_bonus(file, context))
+        .saturating_sub(calculate_config_file_penalty(file, context))
+        .saturating_sub(calculate_hidden_file_penalty(file, context));
+
+    Score {
+        total,
+        base_score: 0,
+        filename_bonus: 0,
+        special_filename_bonus: 0,
+        frecency_boost: total_frecency_score,
+        distance_penalty,
+        similarity_bonus,
+        branch_change_bonus,
+        relation_bonus: 0,
+        match_type,
+        confidence,
+    }
+}
+
+/// The base score of a match relative to `MAX_MATCH_SCORE`. It is not comparable across
+/// queries: short queries can't reach high scores, so a perfect match of a two letter query
+/// still has a low confidence. Bonuses and penalties are left out since they don't say anything
+/// about how well the query matched.
+pub fn match_confidence(base_score: i32) -> f32 {
+    (base_score as f32 / MAX_MATCH_SCORE).clamp(0.0, 1.0)
+}
+
+/// Current file stem and similarity algorithm resolved once per search, without a stem when
+/// the bonus is disabled
+struct SimilarityContext<'a> {
+    current_stem: Option<&'a str>,
+    current_letters: u32,
+    algorithm: SimilarityAlgorithm,
+    weight: f64,
+}
+
+impl<'a> SimilarityContext<'a> {
+    fn new(context: &ScoringContext<'a>) -> Self {
+        let current_stem = context
+            .current_file_data
+            .map(|current_file| current_file.stem)
+            .filter(|stem| !stem.is_empty() && context.filename_similarity_weight > 0.0);
+        let algorithm = SIMILARITY_ALGORITHM
+            .read()
+            .map_or(SimilarityAlgorithm::JaroWinkler, |algorithm| *algorithm);
+
+        Self {
+            current_stem,
+            current_letters: current_stem.map_or(0, ascii_letter_mask),
+            algorithm,
+            weight: context.filename_similarity_weight,
+        }
+    }
+
+    #[inline]
+    fn bonus(&self, file: &FileItem) -> i32 {
+        self.current_stem.map_or(0, |current_stem| {
+            let bonus = similarity_bonus_with_letters(
+                current_stem,
+                self.current_letters,
+                file_stem(&file.file_name),
+                self.algorithm,
+            );
+            (f64::from(bonus) * self.weight).round() as i32
         })
-        .collect()
+    }
+}
+
+#[inline]
+fn current_dir_parts<'a>(context: &ScoringContext<'a>) -> Option<&'a [&'a str]> {
+    context
+        .current_file_data
+        .map(|current_file| current_file.dir_parts.as_slice())
+}
+
+#[inline]
+fn calculate_distance_penalty(current_dir_parts: Option<&[&str]>, file: &FileItem) -> i32 {
+    current_dir_parts.map_or(0, |current_dir_parts| {
+        calculate_directory_distance_penalty(current_dir_parts, &file.directory)
+    })
+}
+
+/// Files under the root directory are all related, so nothing is boosted for a current file there
+#[inline]
+fn calculate_relation_bonus(
+    current_dir_parts: Option<&[&str]>,
+    file: &FileItem,
+    base_score: i32,
+) -> i32 {
+    match current_dir_parts {
+        Some(current_dir_parts)
+            if !current_dir_parts.is_empty()
+                && is_within_directory(current_dir_parts, &file.directory) =>
+        {
+            percent_of(base_score, RELATION_BONUS_PERCENT)
+        }
+        _ => 0,
+    }
+}
+
+/// Frecency scores are `i64`, this keeps them from wrapping around when they become part of a
+/// score
+#[inline]
+fn clamp_to_i32(value: i64) -> i32 {
+    value.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+/// `percent` percent of `value`, multiplied in `i64` so large percentages can't overflow before
+/// the division
+#[inline]
+fn percent_of(value: i32, percent: i64) -> i32 {
+    clamp_to_i32(i64::from(value).saturating_mul(percent) / 100)
+}
+
+#[inline]
+fn calculate_extension_affinity_bonus(
+    file: &FileItem,
+    base_score: i32,
+    context: &ScoringContext,
+) -> i32 {
+    match context.preferred_extensions {
+        Some(preferred) if preferred.contains(&file.extension) => base_score / 10,
+        _ => 0,
+    }
+}
+
+#[inline]
+fn calculate_directory_frecency_bonus(file: &FileItem, context: &ScoringContext) -> i32 {
+    (file.parent_directory_frecency as f64 * context.directory_frecency_weight) as i32
+}
+
+#[inline]
+fn calculate_config_file_penalty(file: &FileItem, context: &ScoringContext) -> i32 {
+    if file.is_config_file {
+        context.config_file_penalty
+    } else {
+        0
+    }
+}
+
+#[inline]
+fn calculate_hidden_file_penalty(file: &FileItem, context: &ScoringContext) -> i32 {
+    if file.is_hidden {
+        context.hidden_file_penalty
+    } else {
+        0
+    }
+}
+
+#[inline]
+fn calculate_branch_change_bonus(file: &FileItem, context: &ScoringContext) -> i32 {
+    match context.branch_changed_files {
+        Some(changed) if changed.contains(&file.path) => context.branch_change_bonus,
+        _ => 0,
+    }
 }
 
 #[inline]
@@ -201,3 +636,217 @@ fn calculate_file_bonus(file: &FileItem, context: &ScoringContext) -> i32 {
 
     bonus
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::types::CurrentFileData;
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+
+    /// Scoring context of a search without a current file or project wide bonuses
+    pub(crate) fn test_scoring_context(query: &str) -> ScoringContext<'_> {
+        ScoringContext {
+            query,
+            current_file: None,
+            current_file_data: None,
+            max_typos: 2,
+            max_threads: 1,
+            branch_changed_files: None,
+            branch_change_bonus: 0,
+            config_file_penalty: 0,
+            hidden_file_penalty: 0,
+            directory_frecency_weight: 0.0,
+            filename_similarity_weight: 0.0,
+            frecency_weight_access: ACCESS_SCORE_WEIGHT,
+            frecency_weight_modification: MODIFICATION_SCORE_WEIGHT,
+            early_exit_threshold: usize::MAX,
+            preferred_extensions: None,
+            chunk_size: DEFAULT_MATCH_CHUNK_SIZE,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            allow_fallback: true,
+        }
+    }
+
+    fn test_files(paths: &[&str]) -> Vec<FileItem> {
+        let base = Path::new("/project");
+        paths
+            .iter()
+            .map(|path| FileItem::new(base.join(path), base, None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_permissive_match_stays_typo_bounded() {
+        let files = test_files(&["src/components/button.tsx", "README.md"]);
+        let match_types = |query| -> Vec<(String, &str)> {
+            match_and_score_files(&files, &test_scoring_context(query))
+                .into_iter()
+                .map(|(idx, score)| (files[idx].relative_path.clone(), score.match_type))
+                .collect()
+        };
+
+        assert_eq!(
+            match_types("buttqqqon"),
+            [("src/components/button.tsx".to_string(), "permissive_fuzzy")]
+        );
+        assert!(match_types("zzzzzzzzzz").is_empty());
+        // one letter short of the query being all typos
+        assert!(match_types("zzz").is_empty());
+    }
+
+    #[test]
+    fn test_early_exit_skips_remaining_chunks() {
+        let paths: Vec<String> = (0..4 * EARLY_EXIT_CHUNK_SIZE)
+            .map(|i| format!("src/components/button_{}.tsx", i))
+            .collect();
+        let files = test_files(&paths.iter().map(String::as_str).collect::<Vec<_>>());
+        let match_count = |early_exit_threshold, max_threads| {
+            match_and_score_files(
+                &files,
+                &ScoringContext {
+                    early_exit_threshold,
+                    max_threads,
+                    ..test_scoring_context("src/components/button")
+                },
+            )
+            .len()
+        };
+
+        assert_eq!(match_count(usize::MAX, 1), files.len());
+        // the first chunk alone has enough matches, every worker matches at most one chunk
+        assert_eq!(match_count(1, 1), EARLY_EXIT_CHUNK_SIZE);
+        assert!(match_count(1, 2) <= 2 * EARLY_EXIT_CHUNK_SIZE);
+        assert_eq!(match_count(usize::MAX - 1, 3), files.len());
+    }
+
+    #[test]
+    fn test_filters_do_not_raise_the_typo_limit() {
+        assert_eq!(default_max_typos("main"), 2);
+        assert_eq!(default_max_typos("src/components/button/main"), 6);
+        assert_eq!(default_max_typos("@dir:src/components/button main"), 2);
+
+        // five typos, matched when the filter token counted towards the typo limit
+        let files = test_files(&["src/api/user.rs", "docs/user.md"]);
+        let query = "@dir:src/api zzzzzuser";
+        let matches = match_and_score_files(
+            &files,
+            &ScoringContext {
+                max_typos: default_max_typos(query),
+                ..test_scoring_context(query)
+            },
+        );
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn test_chunked_matching_finds_the_same_files() {
+        let paths: Vec<String> = (0..300)
+            .map(|i| format!("src/module_{}/file_{}.rs", i % 7, i))
+            .collect();
+        let files = test_files(&paths.iter().map(String::as_str).collect::<Vec<_>>());
+
+        for query in ["file_1", "mod3 fl"] {
+            let matches = |chunk_size| {
+                let mut matches: Vec<(usize, i32)> = match_and_score_files(
+                    &files,
+                    &ScoringContext {
+                        chunk_size,
+                        max_threads: 2,
+                        parallel_threshold: 0,
+                        ..test_scoring_context(query)
+                    },
+                )
+                .into_iter()
+                .map(|(idx, score)| (idx, score.total))
+                .collect();
+                matches.sort_unstable();
+                matches
+            };
+            let unchunked = matches(DEFAULT_MATCH_CHUNK_SIZE);
+            assert!(!unchunked.is_empty());
+            assert_eq!(matches(7), unchunked, "query {}", query);
+        }
+    }
+
+    #[test]
+    fn test_score_arithmetic_saturates() {
+        assert_eq!(clamp_to_i32(i64::MAX), i32::MAX);
+        assert_eq!(clamp_to_i32(i64::MIN), i32::MIN);
+        assert_eq!(clamp_to_i32(-42), -42);
+
+        assert_eq!(percent_of(200, 18), 36);
+        assert_eq!(percent_of(i32::MAX, i64::from(i32::MAX)), i32::MAX);
+        assert_eq!(percent_of(i32::MAX, i64::MIN), i32::MIN);
+        assert_eq!(percent_of(i32::MIN, i64::MIN), i32::MAX);
+    }
+
+    #[test]
+    fn test_literal_matches_are_fully_confident() {
+        let files = test_files(&["src/main.rs", "README.md"]);
+        let confidences = |query| -> Vec<(&str, f32)> {
+            crate::search_mode::SearchMode::Exact
+                .search(&files, query, &test_scoring_context(query))
+                .into_iter()
+                .chain(match_and_score_files(&files, &test_scoring_context("")))
+                .map(|(_, score)| (score.match_type, score.confidence))
+                .collect()
+        };
+        assert_eq!(
+            confidences("main"),
+            [("exact", 1.0), ("frecency", 0.0), ("frecency", 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_branch_change_bonus() {
+        let files = test_files(&["src/user.rs", "lib/user.rs"]);
+        let changed = HashSet::from([PathBuf::from("/project/lib/user.rs")]);
+        let context = ScoringContext {
+            branch_changed_files: Some(&changed),
+            branch_change_bonus: 30,
+            ..test_scoring_context("user")
+        };
+
+        let bonuses: HashMap<&str, i32> = match_and_score_files(&files, &context)
+            .into_iter()
+            .map(|(idx, score)| (files[idx].relative_path.as_str(), score.branch_change_bonus))
+            .collect();
+        assert_eq!(
+            bonuses,
+            HashMap::from([("lib/user.rs", 30), ("src/user.rs", 0)])
+        );
+    }
+
+    #[test]
+    fn test_similarity_bonus_is_opt_in() {
+        let files = test_files(&["src/user_test.rs"]);
+        let current_file = "src/user.rs".to_string();
+        let current_file_data = CurrentFileData::from_path(&current_file);
+        let similarity_bonus = |filename_similarity_weight| {
+            let context = ScoringContext {
+                current_file: Some(&current_file),
+                current_file_data: Some(&current_file_data),
+                filename_similarity_weight,
+                ..test_scoring_context("user")
+            };
+            match_and_score_files(&files, &context)[0]
+                .1
+                .similarity_bonus
+        };
+
+        assert_eq!(similarity_bonus(0.0), 0);
+        let full_bonus = similarity_bonus(1.0);
+        assert!(full_bonus > 0);
+        assert_eq!(similarity_bonus(2.0), full_bonus * 2);
+    }
+
+    #[test]
+    fn test_match_confidence() {
+        assert_eq!(match_confidence(0), 0.0);
+        assert_eq!(match_confidence(-50), 0.0);
+        assert_eq!(match_confidence(255), 1.0);
+        assert_eq!(match_confidence(1000), 1.0);
+        assert!((match_confidence(51) - 0.2).abs() < f32::EPSILON);
+    }
+}
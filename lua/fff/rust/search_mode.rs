@@ -0,0 +1,124 @@
+use crate::path_utils::anchor_component_index;
+use crate::score::{match_and_score_files, match_and_score_query, score_matching_files};
+use crate::types::{FileItem, Score, ScoringContext};
+use rayon::prelude::*;
+use regex::Regex;
+
+/// Score lost per path component before the one matching the anchored query
+const ANCHOR_DEPTH_PENALTY: i32 = 10;
+
+/// Matches `query` against `files`, returning the indices of the matches with their scores
+pub trait Searcher {
+    fn search(files: &[FileItem], query: &str, context: &ScoringContext) -> Vec<(usize, Score)>;
+}
+
+/// Typo tolerant fuzzy matching of the relative path, the default
+pub struct FuzzySearcher;
+
+impl Searcher for FuzzySearcher {
+    fn search(files: &[FileItem], query: &str, context: &ScoringContext) -> Vec<(usize, Score)> {
+        let context = ScoringContext {
+            query,
+            ..context.clone()
+        };
+        match_and_score_files(files, &context)
+    }
+}
+
+/// Relative paths containing `query` as a case sensitive substring
+pub struct ExactSearcher;
+
+impl Searcher for ExactSearcher {
+    fn search(files: &[FileItem], query: &str, context: &ScoringContext) -> Vec<(usize, Score)> {
+        score_matching_files(files, context, "exact", |file| {
+            file.relative_path.contains(query)
+        })
+    }
+}
+
+/// Relative paths matching `query` as a regular expression, nothing matches an invalid one
+pub struct RegexSearcher;
+
+impl Searcher for RegexSearcher {
+    fn search(files: &[FileItem], query: &str, context: &ScoringContext) -> Vec<(usize, Score)> {
+        let regex = match Regex::new(query) {
+            Ok(regex) => regex,
+            Err(e) => {
+                tracing::warn!("Invalid regex search query '{}': {}", query, e);
+                return Vec::new();
+            }
+        };
+
+        score_matching_files(files, context, "regex", |file| {
+            regex.is_match(&file.relative_path)
+        })
+    }
+}
+
+/// Files with a path component starting with `query` (case insensitive), fuzzy scored with
+/// matches in components closer to the root ranking higher
+pub struct AnchoredSearcher;
+
+impl Searcher for AnchoredSearcher {
+    fn search(files: &[FileItem], query: &str, context: &ScoringContext) -> Vec<(usize, Score)> {
+        let anchor = query.trim_start_matches(['/', '\\']).to_lowercase();
+        let (anchored_indices, anchor_depths): (Vec<usize>, Vec<usize>) = files
+            .par_iter()
+            .enumerate()
+            .filter_map(|(idx, file)| {
+                anchor_component_index(&file.relative_path, &anchor).map(|depth| (idx, depth))
+            })
+            .unzip();
+        let anchored_files: Vec<&FileItem> =
+            anchored_indices.iter().map(|&idx| &files[idx]).collect();
+
+        let context = ScoringContext {
+            query: &anchor,
+            ..context.clone()
+        };
+        match_and_score_query(&anchored_files, &context)
+            .into_iter()
+            .map(|(idx, mut score)| {
+                let depth_penalty =
+                    (anchor_depths[idx] as i32).saturating_mul(ANCHOR_DEPTH_PENALTY);
+                score.total = score.total.saturating_sub(depth_penalty);
+                (anchored_indices[idx], score)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    #[default]
+    Fuzzy,
+    Regex,
+    Exact,
+    Anchored,
+}
+
+impl SearchMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "fuzzy" => Some(Self::Fuzzy),
+            "regex" => Some(Self::Regex),
+            "exact" => Some(Self::Exact),
+            "anchored" => Some(Self::Anchored),
+            _ => None,
+        }
+    }
+
+    pub fn search(
+        self,
+        files: &[FileItem],
+        query: &str,
+        context: &ScoringContext,
+    ) -> Vec<(usize, Score)> {
+        match self {
+            Self::Fuzzy => FuzzySearcher::search(files, query, context),
+            Self::Regex => RegexSearcher::search(files, query, context),
+            Self::Exact => ExactSearcher::search(files, query, context),
+            Self::Anchored => AnchoredSearcher::search(files, query, context),
+        }
+    }
+}
@@ -1,11 +1,12 @@
 use crate::error::Error;
+use crate::log_buffer::LogBufferLayer;
 use std::path::Path;
 use tracing_appender::non_blocking;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 static TRACING_INITIALIZED: std::sync::Once = std::sync::Once::new();
 
-/// Initialize tracing with single log file
+/// Initialize tracing with single log file, recent entries are also kept in memory
 ///
 /// # Arguments
 /// * `log_file_path` - Full path to the log file
@@ -56,6 +57,7 @@ pub fn init_tracing(log_file_path: &str, log_level: &str) -> Result<String, Erro
                     .with_line_number(true)
                     .with_ansi(false), // No ANSI colors in log files
             )
+            .with(LogBufferLayer)
             .with(
                 EnvFilter::builder()
                     .with_default_directive(level.into())
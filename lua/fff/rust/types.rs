@@ -1,10 +1,18 @@
 use mlua::prelude::*;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 use crate::git::format_git_status;
+use crate::path_utils::{file_stem, split_path_components};
 
-#[derive(Debug, Clone)]
+/// Spelled as an alias so the serde derive doesn't take `&'static str` fields as borrowed from
+/// the input, which would only allow deserializing from `'static` data
+pub type StaticName = &'static str;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FileItem {
+    /// Not serialized, restore it with `FileItem::resolve_path` after deserializing
+    #[serde(skip)]
     pub path: PathBuf,
     pub relative_path: String,
     pub file_name: String,
@@ -15,11 +23,24 @@ pub struct FileItem {
     pub access_frecency_score: i64,
     pub modification_frecency_score: i64,
     pub total_frecency_score: i64,
+    /// Average access frecency of the files sharing this file's directory
+    pub parent_directory_frecency: i64,
+    #[serde(with = "git_status_bits")]
     pub git_status: Option<git2::Status>,
     pub is_current_file: bool,
+    pub is_test_file: bool,
+    pub is_config_file: bool,
+    pub is_hidden: bool,
+    /// Inode number, 0 when unknown or on platforms without inodes
+    pub inode: u64,
+    /// `FileCategory::name` of the file, for grouping results
+    #[serde(with = "category_name")]
+    pub category: StaticName,
+    /// Line count estimated from size and extension, 0 for binary or large files
+    pub estimated_lines: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Score {
     pub total: i32,
     pub base_score: i32,
@@ -27,15 +48,78 @@ pub struct Score {
     pub special_filename_bonus: i32,
     pub frecency_boost: i32,
     pub distance_penalty: i32,
-    pub match_type: &'static str,
+    pub similarity_bonus: i32,
+    pub branch_change_bonus: i32,
+    /// Bonus for files in the current file's directory or below it
+    pub relation_bonus: i32,
+    #[serde(deserialize_with = "deserialize_match_type")]
+    pub match_type: StaticName,
+    /// How well the query matched in the `[0.0, 1.0]` range, see `score::match_confidence`.
+    /// Always 1 for exact, regex and substring matches which contain the query as is, and 0 for
+    /// files ranked by frecency without a query.
+    #[serde(default)]
+    pub confidence: f32,
+}
+
+/// Parts of the current file's path used when scoring every file, split once per search
+#[derive(Debug, Clone)]
+pub struct CurrentFileData<'a> {
+    pub dir_parts: Vec<&'a str>,
+    pub stem: &'a str,
+}
+
+impl<'a> CurrentFileData<'a> {
+    pub fn from_path(path: &'a str) -> Self {
+        let path = Path::new(path);
+        let dir_parts = path
+            .parent()
+            .and_then(|parent| parent.to_str())
+            .map(|parent| split_path_components(parent).collect())
+            .unwrap_or_default();
+        let stem = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(file_stem)
+            .unwrap_or_default();
+
+        Self { dir_parts, stem }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ScoringContext<'a> {
     pub query: &'a str,
     pub current_file: Option<&'a String>,
+    /// Pre-split `current_file`, set whenever `current_file` is
+    pub current_file_data: Option<&'a CurrentFileData<'a>>,
     pub max_typos: u16,
     pub max_threads: usize,
+    /// Files changed on the current branch since it diverged from upstream
+    pub branch_changed_files: Option<&'a HashSet<PathBuf>>,
+    pub branch_change_bonus: i32,
+    /// Subtracted from the score of configuration files
+    pub config_file_penalty: i32,
+    /// Subtracted from the score of hidden files
+    pub hidden_file_penalty: i32,
+    /// Fraction of `parent_directory_frecency` added to the file's frecency
+    pub directory_frecency_weight: f64,
+    /// Multiplier of the bonus for file names resembling the current file's, 0 disables it
+    pub filename_similarity_weight: f64,
+    /// Multiplier of the access frecency when ranking files without a query
+    pub frecency_weight_access: i32,
+    /// Multiplier of the modification frecency when ranking files without a query
+    pub frecency_weight_modification: i32,
+    /// Stop matching once this many high confidence matches were found, `usize::MAX` disables it
+    pub early_exit_threshold: usize,
+    /// Extensions of the project's main language, matches in them get a small bonus
+    pub preferred_extensions: Option<&'a HashSet<String>>,
+    /// Number of files fuzzy matched together
+    pub chunk_size: usize,
+    /// Haystacks with fewer files than this are matched on the calling thread
+    pub parallel_threshold: usize,
+    /// Whether the permissive typo and substring fallbacks run when nothing matches strictly.
+    /// Searches over chunks of the files turn it off and decide on the fallback for all chunks.
+    pub allow_fallback: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -44,6 +128,124 @@ pub struct SearchResult {
     pub scores: Vec<Score>,
     pub total_matched: usize,
     pub total_files: usize,
+    /// The query these results were produced for
+    pub query: String,
+    /// Set when a timed search hit its deadline before all files were scored
+    pub is_partial: bool,
+    /// `@dir:` filter of the query, items are displayed relative to it
+    pub directory: Option<String>,
+}
+
+/// A query and the file that was opened from its results
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SearchHistoryEntry {
+    pub query: String,
+    pub selected_path: String,
+    /// Unix seconds
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlowDirectory {
+    pub directory: String,
+    pub entry_count: usize,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DirStats {
+    pub file_count: usize,
+    pub total_size: u64,
+    pub modified_count: usize,
+    pub untracked_count: usize,
+}
+
+/// Files are identified by their relative path, scores and metadata are not compared
+impl PartialEq for FileItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.relative_path == other.relative_path
+    }
+}
+
+impl Eq for FileItem {}
+
+impl PartialOrd for FileItem {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FileItem {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.relative_path.cmp(&other.relative_path)
+    }
+}
+
+impl FileItem {
+    /// Rebuilds the absolute path that is skipped during serialization
+    pub fn resolve_path(&mut self, base_path: &std::path::Path) {
+        self.path = base_path.join(&self.relative_path);
+    }
+}
+
+/// Categories are static names, unknown ones deserialize as "other"
+mod category_name {
+    use crate::path_utils::FileCategory;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        category: &&'static str,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(category)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<&'static str, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(FileCategory::from_name(&name)
+            .unwrap_or(FileCategory::Other)
+            .name())
+    }
+}
+
+mod git_status_bits {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        status: &Option<git2::Status>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        status.map(|status| status.bits()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<git2::Status>, D::Error> {
+        Ok(Option::<u32>::deserialize(deserializer)?.map(git2::Status::from_bits_truncate))
+    }
+}
+
+fn deserialize_match_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<&'static str, D::Error> {
+    use serde::Deserialize;
+
+    let match_type = String::deserialize(deserializer)?;
+    [
+        "exact_filename",
+        "fuzzy_filename",
+        "fuzzy_path",
+        "permissive_fuzzy",
+        "substring",
+        "exact",
+        "regex",
+        "frecency",
+    ]
+    .into_iter()
+    .find(|known| *known == match_type)
+    .ok_or_else(|| serde::de::Error::custom(format!("unknown match type '{}'", match_type)))
 }
 
 impl IntoLua for FileItem {
@@ -62,8 +264,14 @@ impl IntoLua for FileItem {
             self.modification_frecency_score,
         )?;
         table.set("total_frecency_score", self.total_frecency_score)?;
+        table.set("parent_directory_frecency", self.parent_directory_frecency)?;
         table.set("git_status", format_git_status(self.git_status))?;
         table.set("is_current_file", self.is_current_file)?;
+        table.set("is_test_file", self.is_test_file)?;
+        table.set("is_config_file", self.is_config_file)?;
+        table.set("is_hidden", self.is_hidden)?;
+        table.set("category", self.category)?;
+        table.set("estimated_lines", self.estimated_lines)?;
         Ok(LuaValue::Table(table))
     }
 }
@@ -77,7 +285,11 @@ impl IntoLua for Score {
         table.set("special_filename_bonus", self.special_filename_bonus)?;
         table.set("frecency_boost", self.frecency_boost)?;
         table.set("distance_penalty", self.distance_penalty)?;
+        table.set("similarity_bonus", self.similarity_bonus)?;
+        table.set("branch_change_bonus", self.branch_change_bonus)?;
+        table.set("relation_bonus", self.relation_bonus)?;
         table.set("match_type", self.match_type)?;
+        table.set("confidence", self.confidence)?;
         Ok(LuaValue::Table(table))
     }
 }
@@ -85,10 +297,68 @@ impl IntoLua for Score {
 impl IntoLua for SearchResult {
     fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
         let table = lua.create_table()?;
-        table.set("items", self.items)?;
+        let items = lua.create_table()?;
+        for item in self.items {
+            let display_path = self
+                .directory
+                .as_deref()
+                .map(|directory| item.relative_to_directory(directory).to_string());
+            let item = item.into_lua(lua)?;
+            if let (Some(display_path), Some(item)) = (display_path, item.as_table()) {
+                item.set("display_path", display_path)?;
+            }
+            items.push(item)?;
+        }
+        table.set("items", items)?;
         table.set("scores", self.scores)?;
         table.set("total_matched", self.total_matched)?;
         table.set("total_files", self.total_files)?;
+        table.set("query", self.query)?;
+        table.set("is_partial", self.is_partial)?;
         Ok(LuaValue::Table(table))
     }
 }
+
+impl IntoLua for SlowDirectory {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let table = lua.create_table()?;
+        table.set("directory", self.directory)?;
+        table.set("entry_count", self.entry_count)?;
+        table.set("elapsed_ms", self.elapsed_ms)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl IntoLua for DirStats {
+    fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        let table = lua.create_table()?;
+        table.set("file_count", self.file_count)?;
+        table.set("total_size", self.total_size)?;
+        table.set("modified_count", self.modified_count)?;
+        table.set("untracked_count", self.untracked_count)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_item_serde_roundtrip() {
+        let base = Path::new("/home/user/project");
+        let mut file = FileItem::new(base.join("src/main.rs"), base, None).unwrap();
+        file.git_status = Some(git2::Status::WT_MODIFIED);
+
+        let json = serde_json::to_value(&file).unwrap();
+        assert!(json.get("path").is_none());
+        assert_eq!(json["git_status"], git2::Status::WT_MODIFIED.bits());
+
+        let mut restored: FileItem = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.path, PathBuf::new());
+        restored.resolve_path(base);
+        assert_eq!(restored.path, file.path);
+        assert_eq!(restored.git_status, file.git_status);
+        assert_eq!(restored.category, file.category);
+    }
+}
@@ -11,10 +11,18 @@ mod file_picker;
 mod frecency;
 #[path = "../../lua/fff/rust/git.rs"]
 mod git;
+#[path = "../../lua/fff/rust/log_buffer.rs"]
+mod log_buffer;
+#[path = "../../lua/fff/rust/memory.rs"]
+mod memory;
 #[path = "../../lua/fff/rust/path_utils.rs"]
 mod path_utils;
+#[path = "../../lua/fff/rust/query.rs"]
+mod query;
 #[path = "../../lua/fff/rust/score.rs"]
 mod score;
+#[path = "../../lua/fff/rust/search_mode.rs"]
+mod search_mode;
 #[path = "../../lua/fff/rust/types.rs"]
 mod types;
 
@@ -38,7 +46,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         env::current_dir()?.to_str().unwrap_or(".").to_string()
     };
 
-    let picker = match FilePicker::new(base_path.clone()) {
+    let picker = match FilePicker::with_file_cache(base_path.clone(), None) {
         Ok(p) => p,
         Err(e) => {
             eprintln!("❌ Failed to create FilePicker: {:?}", e);
@@ -153,7 +161,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         if iteration % 40 == 0 {
-            let search_results = picker.fuzzy_search("rs", 5, 2, None);
+            let search_results = picker.fuzzy_search("rs", 5, 2, None, None);
             let timestamp = chrono::Local::now().format("%H:%M:%S");
             println!(
                 "🔍 [{}] Search test 'rs': {} matches",